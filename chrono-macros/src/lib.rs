@@ -2,6 +2,18 @@ use proc_macro::TokenStream;
 
 mod macros;
 
+/// Turn an async function into a task with its own static pool of memory.
+/// Calling the function returns a `Permit` to pass to `chrono::spawn`.
+/// Takes an optional pool `size` (default 1), a task `name` and `arena` to
+/// draw memory from the shared arena instead of a pool
+///
+/// The function must be a free function. It can't be used on functions in an
+/// `impl` block, since the type of the task's future is declared next to it
+///
+/// ```ignore
+/// #[chrono::alloc(size = 4)]
+/// async fn handle_conn(socket: TcpSocket<'static>) { /* ... */ }
+/// ```
 #[proc_macro_attribute]
 pub fn alloc(args: TokenStream, item: TokenStream) -> TokenStream {
     let f = syn::parse_macro_input!(item);
@@ -14,3 +26,22 @@ pub fn main(_: TokenStream, item: TokenStream) -> TokenStream {
     let f = syn::parse_macro_input!(item);
     macros::main::main(f)
 }
+
+/// Wait on several futures and run the handler of the first to complete.
+/// Branches are polled in a random order unless the first line is `biased;`.
+/// A branch whose output doesn't match its pattern is disabled, and `else`
+/// runs once every branch is disabled
+///
+/// ```ignore
+/// chrono::select! {
+///     Ok(n) = socket.read(&mut buf) => { /* ... */ }
+///     Some(cmd) = rx.recv() => { /* ... */ }
+///     _ = sleep(Duration::from_secs(5)) => { /* ... */ }
+///     else => { /* ... */ }
+/// }
+/// ```
+#[proc_macro]
+pub fn select(input: TokenStream) -> TokenStream {
+    let select = syn::parse_macro_input!(input);
+    macros::select::select(select)
+}
@@ -1,96 +1,227 @@
 use darling::FromMeta;
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::ReturnType;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{FnArg, GenericParam, Pat, ReturnType, Type};
 
-#[derive(Debug, FromMeta)]
+#[derive(Debug, Default, FromMeta)]
 struct Args {
     #[darling(default)]
     size: Option<usize>,
+    #[darling(default)]
+    name: Option<String>,
+    #[darling(default)]
+    arena: bool,
 }
 
 pub(crate) fn alloc(args: syn::AttributeArgs, f: syn::ItemFn) -> TokenStream {
-    let mut arg_names = Vec::new();
-    let mut fn_args = f.sig.inputs.clone();
+    // On bad arguments the task is still expanded with the defaults, so its
+    // callers don't pile more errors on top
+    let (args, mut tokens) = match Args::from_list(&args) {
+        Ok(args) => (args, TokenStream2::new()),
+        Err(e) => (Args::default(), e.write_errors()),
+    };
+
+    match expand(args, f) {
+        Ok(expanded) => tokens.extend(expanded),
+        Err(e) => tokens.extend(e.to_compile_error()),
+    }
+    tokens.into()
+}
+
+fn expand(args: Args, f: syn::ItemFn) -> syn::Result<TokenStream2> {
+    if f.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            f.sig.fn_token.span(),
+            "task functions must be `async`",
+        ));
+    }
 
-    let args = Args::from_list(&args)
-        .map_err(|e| e.write_errors())
-        .unwrap();
     let size = args.size.unwrap_or(1);
+    if size == 0 {
+        return Err(syn::Error::new(
+            f.sig.ident.span(),
+            "the task pool must have a `size` of at least 1",
+        ));
+    }
 
-    for arg in fn_args.iter_mut() {
+    // A static pool can't depend on generic parameters, so generic tasks have
+    // to draw their memory from the arena
+    let generic = f.sig.generics.params.iter().find(|p| !matches!(p, GenericParam::Lifetime(_)));
+    let impl_arg = f.sig.inputs.iter().find_map(|arg| match arg {
+        FnArg::Typed(t) if contains_impl_trait(&t.ty) => Some(t.ty.span()),
+        _ => None,
+    });
+    let is_generic = generic.is_some() || impl_arg.is_some();
+    if !args.arena {
+        if let Some(span) = generic.map(|g| g.span()).or(impl_arg) {
+            return Err(syn::Error::new(
+                span,
+                "generic task functions need `#[chrono::alloc(arena)]` since a static pool \
+                 can't depend on generic parameters",
+            ));
+        }
+    }
+
+    if let Some(lifetime) = f.sig.generics.lifetimes().next() {
+        return Err(syn::Error::new(
+            lifetime.span(),
+            "tasks can't borrow from their caller. Use `'static` instead",
+        ));
+    }
+
+    // Every argument is moved into the task. Patterns are bound to plain
+    // identifiers in the outer function and destructured by the inner one
+    let mut arg_names = Vec::new();
+    let mut fn_args = f.sig.inputs.clone();
+    for (i, arg) in fn_args.iter_mut().enumerate() {
         match arg {
-            syn::FnArg::Receiver(_) => {}
-            syn::FnArg::Typed(t) => {
-                if let syn::Pat::Ident(id) = t.pat.as_mut() {
-                    arg_names.push(id.ident.clone());
-                    id.mutability = None;
-                }
+            FnArg::Receiver(r) => {
+                return Err(syn::Error::new(
+                    r.span(),
+                    "task functions can't take `self`",
+                ));
+            }
+            FnArg::Typed(t) => {
+                check_static(&t.ty)?;
+
+                let ident = match t.pat.as_ref() {
+                    Pat::Ident(id) if id.subpat.is_none() => id.ident.clone(),
+                    _ => format_ident!("__arg{}", i),
+                };
+                *t.pat = syn::parse_quote!(#ident);
+                arg_names.push(ident);
             }
         }
     }
 
-    let inputs = f.sig.inputs.clone();
     let output = f.sig.output.clone();
+    let generics = f.sig.generics.clone();
+    let where_clause = &generics.where_clause;
 
     let fn_name = f.sig.ident.clone();
     let inner_fn_name = format_ident!("__{}_task", fn_name);
+    let task_name = args.name.unwrap_or_else(|| fn_name.to_string());
     let mut inner_fn = f;
 
-    let fn_args = inputs;
     let visibility = inner_fn.vis.clone();
     inner_fn.vis = syn::Visibility::Inherited;
     inner_fn.sig.ident = inner_fn_name.clone();
 
-    let impl_ty = {
-        match output.clone() {
-            ReturnType::Default => {
-                quote!(impl ::core::future::Future<Output = ()>)
-            }
-            ReturnType::Type(_, ret) => {
-                quote!(impl ::core::future::Future<Output = #ret>)
-            }
-        }
+    let ret = match output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ret) => quote!(#ret),
     };
 
-    let fn_ret = {
-        match output.clone() {
-            ReturnType::Default => {
-                quote!(::chrono::task::Permit<#impl_ty, ()>)
-            }
-            ReturnType::Type(_, ret) => {
-                quote!(::chrono::task::Permit<#impl_ty, #ret>)
+    // Generic tasks can't name their future outside of the function, so
+    // they have no footprint
+    if is_generic {
+        return Ok(quote! {
+            #inner_fn
+
+            #visibility fn #fn_name #generics (#fn_args) -> ::chrono::task::Permit<impl ::core::future::Future<Output = #ret>, #ret>
+            #where_clause
+            {
+                let permit = ::chrono::task::Permit::from_arena(move || #inner_fn_name(#(#arg_names,)*));
+                permit.name(#task_name)
             }
-        }
+        });
+    }
+
+    // The future is named at module level so the footprint of the task can
+    // be computed outside of the function. Inside an `impl` block the alias
+    // is an associated type, which isn't allowed, so the error points at
+    // the function
+    let future_ty = format_ident!("__{}_Future", fn_name);
+    let future_alias = quote_spanned! {fn_name.span()=>
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #visibility type #future_ty = impl ::core::future::Future<Output = #ret>;
     };
+    let footprint = format_ident!("{}_FOOTPRINT", fn_name.to_string().to_uppercase());
+    let fn_ret = quote!(::chrono::task::Permit<#future_ty, #ret>);
 
-    let memory_type = {
-        match output {
-            ReturnType::Default => {
-                quote!(Memory<F, ()>)
-            }
-            ReturnType::Type(_, ret) => {
-                quote!(Memory<F, #ret>)
-            }
-        }
+    // Tasks drawing from the shared arena don't need a static pool
+    let (pool_size, permit) = if args.arena {
+        let permit = quote! {
+            let permit = ::chrono::task::Permit::from_arena(move || #inner_fn_name(#(#arg_names,)*));
+        };
+        (0, permit)
+    } else {
+        let permit = quote! {
+            use ::chrono::task::Memory;
+
+            const ALLOC: Memory<#future_ty, #ret> = Memory::alloc();
+            static MEMORY: [Memory<#future_ty, #ret>; #size] = [ALLOC; #size];
+            let permit = ::chrono::task::Permit::new(&MEMORY, move || #inner_fn_name(#(#arg_names,)*));
+        };
+        (size, permit)
     };
 
-    quote! {
+    Ok(quote! {
         #inner_fn
 
-        #visibility fn #fn_name(#fn_args) -> #fn_ret {
-            use ::chrono::task::Memory;
+        #future_alias
 
-            type F = #impl_ty;
+        /// RAM taken by the task pool
+        #visibility const #footprint: ::chrono::task::Footprint =
+            ::chrono::task::Footprint::new::<#future_ty, #ret>(#pool_size);
 
-            fn launder_tait(task: #fn_ret) -> #fn_ret {
-                task
-            }
+        #visibility fn #fn_name(#fn_args) -> #fn_ret #where_clause {
+            // Picked up from the final binary by the task footprint tool.
+            // `chrono.x` keeps the section, which is not loaded onto the device
+            #[used]
+            #[link_section = ".chrono_footprint"]
+            static __CHRONO_FOOTPRINT: [usize; 2] = [#footprint.size, #footprint.count];
+
+            #permit
+            permit.name(#task_name)
+        }
+    })
+}
 
-            const ALLOC: #memory_type = Memory::alloc();
-            static MEMORY: [#memory_type; #size] = [ALLOC; #size];
-            launder_tait(::chrono::task::Permit::new(&MEMORY, move || #inner_fn_name(#(#arg_names,)*)))
+/// Whether `ty` contains an `impl Trait`, which makes the function generic
+fn contains_impl_trait(ty: &Type) -> bool {
+    match ty {
+        Type::ImplTrait(_) => true,
+        Type::Reference(r) => contains_impl_trait(&r.elem),
+        Type::Paren(p) => contains_impl_trait(&p.elem),
+        Type::Group(g) => contains_impl_trait(&g.elem),
+        Type::Array(a) => contains_impl_trait(&a.elem),
+        Type::Slice(s) => contains_impl_trait(&s.elem),
+        Type::Tuple(t) => t.elems.iter().any(contains_impl_trait),
+        Type::Path(p) => p.path.segments.iter().any(|s| match &s.arguments {
+            syn::PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| match arg {
+                syn::GenericArgument::Type(ty) => contains_impl_trait(ty),
+                _ => false,
+            }),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Tasks outlive the function that spawns them, so references must be `'static`
+fn check_static(ty: &Type) -> syn::Result<()> {
+    match ty {
+        Type::Reference(r) => {
+            match &r.lifetime {
+                Some(lifetime) if lifetime.ident == "static" => {}
+                _ => {
+                    return Err(syn::Error::new(
+                        r.span(),
+                        "references passed to a task must be `&'static`",
+                    ))
+                }
+            }
+            check_static(&r.elem)
         }
+        Type::Paren(p) => check_static(&p.elem),
+        Type::Group(g) => check_static(&g.elem),
+        Type::Array(a) => check_static(&a.elem),
+        Type::Slice(s) => check_static(&s.elem),
+        Type::Tuple(t) => t.elems.iter().try_for_each(check_static),
+        _ => Ok(()),
     }
-    .into()
 }
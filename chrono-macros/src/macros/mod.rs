@@ -1,2 +1,3 @@
 pub(crate) mod main;
-pub(crate) mod alloc;
\ No newline at end of file
+pub(crate) mod alloc;
+pub(crate) mod select;
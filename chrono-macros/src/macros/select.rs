@@ -0,0 +1,225 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Pat, Token};
+
+/// Maximum number of branches. Disabled branches are tracked in a `u64`
+const MAX_BRANCHES: usize = 64;
+
+pub(crate) struct Select {
+    biased: bool,
+    branches: Vec<Branch>,
+    else_branch: Option<Expr>,
+}
+
+struct Branch {
+    pat: Pat,
+    future: Expr,
+    handler: Expr,
+}
+
+impl Parse for Select {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut biased = false;
+        if input.peek(syn::Ident) && input.peek2(Token![;]) {
+            let ident: syn::Ident = input.parse()?;
+            if ident != "biased" {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `biased;` or a branch",
+                ));
+            }
+            input.parse::<Token![;]>()?;
+            biased = true;
+        }
+
+        let mut branches = Vec::new();
+        let mut else_branch = None;
+
+        while !input.is_empty() {
+            if input.peek(Token![else]) {
+                let token = input.parse::<Token![else]>()?;
+                if else_branch.is_some() {
+                    return Err(syn::Error::new(
+                        token.span,
+                        "only one `else` branch is allowed",
+                    ));
+                }
+                input.parse::<Token![=>]>()?;
+                else_branch = Some(parse_handler(input)?);
+                continue;
+            }
+
+            let pat: Pat = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let future: Expr = input.parse()?;
+            input.parse::<Token![=>]>()?;
+            let handler = parse_handler(input)?;
+
+            branches.push(Branch {
+                pat,
+                future,
+                handler,
+            });
+        }
+
+        if branches.is_empty() {
+            return Err(input.error("`select!` needs at least one branch"));
+        }
+        if branches.len() > MAX_BRANCHES {
+            return Err(input.error(format!(
+                "`select!` supports at most {} branches",
+                MAX_BRANCHES
+            )));
+        }
+
+        Ok(Select {
+            biased,
+            branches,
+            else_branch,
+        })
+    }
+}
+
+/// Parse the handler of a branch and the comma after it. Like match arms,
+/// the comma is optional after a block
+fn parse_handler(input: ParseStream) -> syn::Result<Expr> {
+    let handler: Expr = input.parse()?;
+    let is_block = matches!(handler, Expr::Block(_));
+
+    if input.peek(Token![,]) {
+        input.parse::<Token![,]>()?;
+    } else if !is_block && !input.is_empty() {
+        return Err(input.error("expected `,` after the branch"));
+    }
+
+    Ok(handler)
+}
+
+pub(crate) fn select(select: Select) -> TokenStream {
+    let count = select.branches.len();
+    let variants: Vec<_> = (0..count).map(|i| format_ident!("_{}", i)).collect();
+    let types: Vec<_> = (0..count).map(|i| format_ident!("T{}", i)).collect();
+    let indices: Vec<_> = (0..count).map(syn::Index::from).collect();
+    let futures = select.branches.iter().map(|b| &b.future);
+
+    // Poll a branch. Its output is checked against the pattern so a branch
+    // whose output does not match is disabled and the others keep running.
+    // The check borrows the output, which is only bound by the handler
+    let polls = select.branches.iter().enumerate().map(|(i, branch)| {
+        let mut pat = branch.pat.clone();
+        clean_pattern(&mut pat);
+        let index = &indices[i];
+        let variant = &variants[i];
+        quote! {
+            #i => {
+                if __disabled & (1 << #i) != 0 {
+                    continue;
+                }
+
+                let __future = unsafe { ::core::pin::Pin::new_unchecked(&mut __futures.#index) };
+                if let ::core::task::Poll::Ready(__output) = ::core::future::Future::poll(__future, __cx) {
+                    #[allow(unused_variables, unreachable_patterns)]
+                    let __matches = ::core::matches!(&__output, #pat);
+                    if __matches {
+                        return ::core::task::Poll::Ready(__Output::#variant(__output));
+                    }
+                    __disabled |= 1 << #i;
+                }
+            }
+        }
+    });
+
+    let handlers = select.branches.iter().enumerate().map(|(i, branch)| {
+        let pat = &branch.pat;
+        let handler = &branch.handler;
+        let variant = &variants[i];
+        quote! {
+            __Output::#variant(#pat) => #handler,
+            __Output::#variant(_) => ::core::unreachable!(),
+        }
+    });
+
+    let start = if select.biased {
+        quote!(0)
+    } else {
+        quote!(::chrono::future::__private::random(#count))
+    };
+
+    let else_handler = match select.else_branch {
+        Some(handler) => quote!(#handler),
+        None => quote!(::core::panic!(
+            "all branches of `select!` are disabled and there is no `else` branch"
+        )),
+    };
+
+    let expanded: TokenStream2 = quote! {{
+        #[allow(dead_code)]
+        enum __Output<#(#types,)*> {
+            #(#variants(#types),)*
+            Disabled,
+        }
+
+        // The futures are dropped before the handler runs so it can use
+        // whatever they borrowed
+        #[allow(clippy::modulo_one)]
+        let __output = {
+            let mut __futures = (#(#futures,)*);
+            let mut __disabled: u64 = 0;
+
+            ::core::future::poll_fn(|__cx| {
+                let __start = #start;
+                for __i in 0..#count {
+                    match (__start + __i) % #count {
+                        #(#polls)*
+                        _ => ::core::unreachable!(),
+                    }
+                }
+
+                if __disabled.count_ones() as usize == #count {
+                    return ::core::task::Poll::Ready(__Output::Disabled);
+                }
+                ::core::task::Poll::Pending
+            })
+            .await
+        };
+
+        #[allow(unreachable_patterns)]
+        match __output {
+            #(#handlers)*
+            __Output::Disabled => #else_handler,
+        }
+    }};
+
+    expanded.into()
+}
+
+/// Remove `mut` and `ref` from the bindings of a pattern so it can be matched
+/// against a reference to the output without moving out of it
+fn clean_pattern(pat: &mut Pat) {
+    match pat {
+        Pat::Ident(ident) => {
+            ident.by_ref = None;
+            ident.mutability = None;
+            if let Some((_, pat)) = &mut ident.subpat {
+                clean_pattern(pat);
+            }
+        }
+        Pat::Or(or) => or.cases.iter_mut().for_each(clean_pattern),
+        Pat::Reference(reference) => {
+            reference.mutability = None;
+            clean_pattern(&mut reference.pat);
+        }
+        Pat::Slice(slice) => slice.elems.iter_mut().for_each(clean_pattern),
+        Pat::Struct(strukt) => strukt
+            .fields
+            .iter_mut()
+            .for_each(|field| clean_pattern(&mut field.pat)),
+        Pat::Tuple(tuple) => tuple.elems.iter_mut().for_each(clean_pattern),
+        Pat::TupleStruct(tuple) => tuple.pat.elems.iter_mut().for_each(clean_pattern),
+        Pat::Box(boxed) => clean_pattern(&mut boxed.pat),
+        Pat::Type(typed) => clean_pattern(&mut typed.pat),
+        _ => {}
+    }
+}
@@ -0,0 +1,79 @@
+// The expansion of `select!` refers to `::chrono`
+extern crate self as chrono;
+
+use core::future::{ready, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use chrono_macros::select;
+
+pub mod future {
+    pub mod __private {
+        pub fn random(_: usize) -> usize {
+            0
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct NonCopy(u32);
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |p| RawWaker::new(p, &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut Context::from_waker(&waker)) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn binding_named_output() {
+    let value = block_on(async {
+        select! {
+            Some(output) = ready(Some(1u32)) => output,
+        }
+    });
+    assert_eq!(value, 1);
+}
+
+#[test]
+fn mutable_binding_of_non_copy_output() {
+    let value = block_on(async {
+        select! {
+            Some(mut m) = ready(Some(NonCopy(1))) => {
+                m.0 += 1;
+                m
+            }
+        }
+    });
+    assert_eq!(value, NonCopy(2));
+}
+
+#[test]
+fn mismatched_branch_is_disabled() {
+    let value = block_on(async {
+        select! {
+            biased;
+            Some(n) = ready(None::<u32>) => n,
+            Ok(n) = ready(Ok::<u32, ()>(2)) => n,
+            else => 0,
+        }
+    });
+    assert_eq!(value, 2);
+
+    let value = block_on(async {
+        select! {
+            Some(n) = ready(None::<u32>) => n,
+            else => 3,
+        }
+    });
+    assert_eq!(value, 3);
+}
@@ -0,0 +1,12 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    // Put the linker script where the linker can find it, so binaries can
+    // link with `-Tchrono.x`
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::copy("chrono.x", out.join("chrono.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=chrono.x");
+}
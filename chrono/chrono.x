@@ -0,0 +1,9 @@
+/* Sections read from the firmware ELF by the host tools. They are not loaded
+   onto the device, and are kept even though nothing references them */
+SECTIONS
+{
+  .chrono_footprint 0 (INFO) :
+  {
+    KEEP(*(.chrono_footprint .chrono_footprint.*));
+  }
+}
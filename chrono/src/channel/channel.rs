@@ -1,9 +1,16 @@
 use core::cell::RefCell;
+use core::pin::Pin;
 use core::task::{Context, Poll, Waker};
 
-use heapless::Deque;
+use heapless::{Deque, Vec};
 
 use super::error::{SendError, TryRecvError};
+use crate::sync::wait_queue::{WaitQueue, Waiter};
+
+/// Maximum number of senders waiting for space in a channel, and of tasks
+/// waiting for it to close. Tasks past this limit poll again instead of
+/// waiting to be woken. Senders are served in the order they started waiting
+pub const MAX_WAITING_SENDERS: usize = 8;
 
 pub struct Channel<T, const N: usize> {
     /// Inner state of the channel
@@ -14,12 +21,19 @@ struct Inner<T, const N: usize> {
     /// Queue holding messages
     queue: Deque<T, N>,
     /// Number of outstanding sender handles. When it drops to
-    /// zero, we close the sending half of the channel
+    /// zero, we close the channel
     tx_count: usize,
     /// State of the channel
     state: State,
     /// Waker notified when items are pushed into the channel
     rx_waker: Option<Waker>,
+    /// Slots reserved by senders but not yet filled
+    reserved: usize,
+    /// Senders waiting for space in the channel
+    tx_waiters: WaitQueue<MAX_WAITING_SENDERS>,
+    /// Tasks waiting for the channel to close. Only woken by a close, so
+    /// messages coming and going don't disturb them
+    closed_waiters: Vec<Waker, MAX_WAITING_SENDERS>,
 }
 
 enum State {
@@ -34,14 +48,16 @@ impl<T, const N: usize> Channel<T, N> {
         Channel {
             inner: RefCell::new(Inner {
                 queue: Deque::new(),
-                tx_count: 1,
+                tx_count: 0,
                 state: State::Open,
                 rx_waker: None,
+                reserved: 0,
+                tx_waiters: WaitQueue::new(),
+                closed_waiters: Vec::new(),
             }),
         }
     }
 
-    #[allow(unused)]
     fn wake_rx(&self) {
         let mut inner = self.inner.borrow_mut();
         if let Some(waker) = inner.rx_waker.take() {
@@ -49,50 +65,184 @@ impl<T, const N: usize> Channel<T, N> {
         }
     }
 
+    fn register(waiters: &mut Vec<Waker, MAX_WAITING_SENDERS>, waker: &Waker) {
+        if !waiters.iter().any(|w| w.will_wake(waker)) && waiters.push(waker.clone()).is_err() {
+            // The wait list is full. Poll again rather than miss the wakeup
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Stop accepting messages. Messages already in the channel can still
+    /// be received
     pub fn close(&self) {
         let mut inner = self.inner.borrow_mut();
         inner.state = State::Closed;
+        // Waiting senders have to learn the channel is closed, and a waiting
+        // receiver that no more messages are coming
+        inner.tx_waiters.notify_all();
+        for waker in core::mem::take(&mut inner.closed_waiters) {
+            waker.wake();
+        }
+        drop(inner);
+        self.wake_rx();
     }
 
-    pub fn incr_tx_count(&self) {
+    pub fn is_closed(&self) -> bool {
+        matches!(self.inner.borrow().state, State::Closed)
+    }
+
+    /// Wait for the channel to close
+    pub fn poll_closed(&self, cx: &mut Context) -> Poll<()> {
         let mut inner = self.inner.borrow_mut();
-        inner.tx_count += 1;
+        match inner.state {
+            State::Closed => Poll::Ready(()),
+            State::Open => {
+                Self::register(&mut inner.closed_waiters, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.inner.borrow().queue.len()
     }
 
-    pub fn decr_tx_count(&self) {
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().queue.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Count a new sender handle
+    pub(crate) fn add_sender(&self) {
+        self.inner.borrow_mut().tx_count += 1;
+    }
+
+    /// Forget a sender handle. The channel closes once the last one is gone
+    pub(crate) fn remove_sender(&self) {
         let mut inner = self.inner.borrow_mut();
         inner.tx_count -= 1;
+        if inner.tx_count == 0 {
+            drop(inner);
+            self.close();
+        }
     }
 
     pub fn tx_count(&self) -> usize {
         self.inner.borrow().tx_count
     }
 
-    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+    pub fn try_send(&self, message: T) -> Result<(), SendError<T>> {
         let mut inner = self.inner.borrow_mut();
         match inner.state {
-            State::Open => match inner.queue.push_back(message) {
-                Ok(_) => {
-                    // If there is a receiver waiting for a message, notify
-                    // that a message has been sent on the channel
-                    if let Some(rx_waker) = &inner.rx_waker {
-                        rx_waker.wake_by_ref();
-                    }
-                    Ok(())
+            State::Open => {
+                // Reserved slots are spoken for even though they are empty
+                if inner.queue.len() + inner.reserved >= N {
+                    return Err(SendError::Full(message));
                 }
-                Err(message) => Err(SendError::Full(message)),
-            },
+                Self::push(&mut inner, message);
+                Ok(())
+            }
             State::Closed => Err(SendError::Closed(message)),
         }
     }
 
+    /// Reserve a slot in the channel. If there is none, or other senders
+    /// are waiting for one, the sender waits in line until a slot frees up
+    ///
+    /// # Safety
+    ///
+    /// The waiter must be removed with [`remove_tx_waiter`](Self::remove_tx_waiter)
+    /// before it is dropped, and must not wait on another channel
+    pub(crate) unsafe fn poll_reserve(
+        &self,
+        cx: &mut Context,
+        waiter: Pin<&Waiter>,
+    ) -> Poll<Result<(), SendError<()>>> {
+        let mut inner = self.inner.borrow_mut();
+        if let State::Closed = inner.state {
+            inner.tx_waiters.remove(waiter);
+            return Poll::Ready(Err(SendError::Closed(())));
+        }
+
+        // Wait behind the senders already in line
+        let turn = match waiter.is_idle() {
+            true => !inner.tx_waiters.has_waiters(),
+            false => waiter.is_notified(),
+        };
+        if turn && inner.queue.len() + inner.reserved < N {
+            inner.tx_waiters.remove(waiter);
+            inner.reserved += 1;
+            // Let the next sender in line know if there is a slot left
+            if inner.queue.len() + inner.reserved < N {
+                inner.tx_waiters.notify_one();
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        inner.tx_waiters.wait(waiter, cx.waker());
+        Poll::Pending
+    }
+
+    /// Stop waiting for a slot, passing on a wakeup the sender won't act on
+    ///
+    /// # Safety
+    ///
+    /// The waiter must be idle or waiting on this channel
+    pub(crate) unsafe fn remove_tx_waiter(&self, waiter: Pin<&Waiter>) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.tx_waiters.remove(waiter) {
+            inner.tx_waiters.notify_one();
+        }
+    }
+
+    /// Send a message into a slot reserved with [`poll_reserve`](Self::poll_reserve).
+    /// The message is handed back if the channel has closed since
+    pub(crate) fn send_reserved(&self, message: T) -> Result<(), SendError<T>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.reserved -= 1;
+        match inner.state {
+            State::Open => {
+                Self::push(&mut inner, message);
+                Ok(())
+            }
+            State::Closed => Err(SendError::Closed(message)),
+        }
+    }
+
+    /// Give back a slot reserved with [`poll_reserve`](Self::poll_reserve)
+    pub(crate) fn release_reserved(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.reserved -= 1;
+        inner.tx_waiters.notify_one();
+    }
+
+    fn push(inner: &mut Inner<T, N>, message: T) {
+        // Cannot fail since callers checked there is room
+        let _ = inner.queue.push_back(message);
+
+        // If there is a receiver waiting for a message, notify
+        // that a message has been sent on the channel
+        if let Some(rx_waker) = &inner.rx_waker {
+            rx_waker.wake_by_ref();
+        }
+    }
+
     pub fn poll_recv(&self, cx: &mut Context) -> Poll<Option<T>> {
         let mut inner = self.inner.borrow_mut();
         match inner.queue.pop_front() {
             // If there is a message, regardless if the channel is closed,
             // we read the message. This allows us to read any outstanding
             // messages in the event the channel is closed
-            Some(message) => Poll::Ready(Some(message)),
+            Some(message) => {
+                // One slot freed up, so one sender can go
+                inner.tx_waiters.notify_one();
+                Poll::Ready(Some(message))
+            }
             // If the channel is still open, then we know it's just
             // empty temporarily and could be populated in future. We
             // register the rx waker to be woken when a new task is pushed
@@ -121,7 +271,10 @@ impl<T, const N: usize> Channel<T, N> {
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         let mut inner = self.inner.borrow_mut();
         match inner.queue.pop_front() {
-            Some(message) => Ok(message),
+            Some(message) => {
+                inner.tx_waiters.notify_one();
+                Ok(message)
+            }
             None => match inner.state {
                 State::Open => Err(TryRecvError::Empty),
                 State::Closed => Err(TryRecvError::Disconnected),
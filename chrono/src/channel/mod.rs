@@ -2,4 +2,5 @@ mod channel;
 pub use channel::Channel;
 
 pub mod error;
+pub mod mpmc;
 pub mod mpsc;
@@ -0,0 +1,529 @@
+//! A bounded multi-producer, multi-consumer queue for sending values between
+//! asynchronous tasks. Each message is received by exactly one receiver.
+//!
+//! Waiting senders and receivers queue up in fixed-capacity wait lists of `W`
+//! tasks each, and are served in the order they started waiting. A task only
+//! skips the line if nobody is waiting. `try_send` and `try_recv` never wait
+//! and so don't queue up
+//!
+//! ```ignore
+//! static JOBS: mpmc::Channel<Job, 8, 4> = mpmc::channel();
+//!
+//! let (tx, rx) = mpmc::split(&JOBS);
+//! for _ in 0..4 {
+//!     chrono::spawn(worker(rx.clone()))?;
+//! }
+//! ```
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use heapless::Deque;
+
+use crate::channel::error::{SendError, TryRecvError};
+use crate::sync::wait_queue::{WaitQueue, Waiter};
+use crate::task::coop;
+
+/// Default number of senders and of receivers that can wait on a channel
+pub const DEFAULT_WAITERS: usize = 4;
+
+pub struct Channel<T, const N: usize, const W: usize = DEFAULT_WAITERS> {
+    /// Inner state of the channel
+    inner: RefCell<Inner<T, N, W>>,
+}
+
+struct Inner<T, const N: usize, const W: usize> {
+    /// Queue holding messages
+    queue: Deque<T, N>,
+    /// Number of outstanding sender handles
+    tx_count: usize,
+    /// Number of outstanding receiver handles
+    rx_count: usize,
+    /// The channel is closed once either side is gone
+    closed: bool,
+    /// Receivers waiting for a message
+    receivers: WaitQueue<W>,
+    /// Senders waiting for space in the channel
+    senders: WaitQueue<W>,
+}
+
+pub const fn channel<T, const N: usize, const W: usize>() -> Channel<T, N, W> {
+    Channel::new()
+}
+
+/// Takes a [Channel] and splits it into Sender and Receiver halves. Both
+/// can be cloned
+pub fn split<T, const N: usize, const W: usize>(
+    chan: &Channel<T, N, W>,
+) -> (Sender<T, N, W>, Receiver<T, N, W>) {
+    (Sender::new(chan), Receiver::new(chan))
+}
+
+pub struct Sender<'ch, T, const N: usize, const W: usize = DEFAULT_WAITERS> {
+    chan: &'ch Channel<T, N, W>,
+}
+
+pub struct Receiver<'ch, T, const N: usize, const W: usize = DEFAULT_WAITERS> {
+    chan: &'ch Channel<T, N, W>,
+}
+
+/// Future returned by [`Receiver::recv`]
+pub struct Recv<'a, T, const N: usize, const W: usize> {
+    chan: &'a Channel<T, N, W>,
+    /// Place in the receivers' wait list. It only waits there, and leaves
+    /// when the future is dropped
+    waiter: Waiter,
+}
+
+/// Future waiting for space in the channel
+struct Reserve<'a, T, const N: usize, const W: usize> {
+    chan: &'a Channel<T, N, W>,
+    /// Place in the senders' wait list. It only waits there, and leaves
+    /// when the future is dropped
+    waiter: Waiter,
+}
+
+// ===== impl Channel =====
+
+impl<T, const N: usize, const W: usize> Channel<T, N, W> {
+    pub const fn new() -> Channel<T, N, W> {
+        Channel {
+            inner: RefCell::new(Inner {
+                queue: Deque::new(),
+                tx_count: 0,
+                rx_count: 0,
+                closed: false,
+                receivers: WaitQueue::new(),
+                senders: WaitQueue::new(),
+            }),
+        }
+    }
+
+    /// Stop accepting messages and wake every waiting task. Messages already
+    /// in the channel can still be received
+    pub fn close(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.closed = true;
+        inner.receivers.notify_all();
+        inner.senders.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.borrow().closed
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().queue.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn try_send(&self, message: T) -> Result<(), SendError<T>> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.closed {
+            return Err(SendError::Closed(message));
+        }
+
+        match inner.queue.push_back(message) {
+            Ok(()) => {
+                inner.receivers.notify_one();
+                Ok(())
+            }
+            Err(message) => Err(SendError::Full(message)),
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut inner = self.inner.borrow_mut();
+        match inner.queue.pop_front() {
+            Some(message) => {
+                inner.senders.notify_one();
+                Ok(message)
+            }
+            None if inner.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+unsafe impl<T, const N: usize, const W: usize> Sync for Channel<T, N, W> {}
+
+// ==== impl Sender =====
+
+impl<'ch, T, const N: usize, const W: usize> Sender<'ch, T, N, W> {
+    fn new(chan: &'ch Channel<T, N, W>) -> Sender<'ch, T, N, W> {
+        chan.inner.borrow_mut().tx_count += 1;
+        Sender { chan }
+    }
+
+    /// Send a message, waiting for space if the channel is full. Fails if
+    /// the channel is closed
+    pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+        let reserve = Reserve {
+            chan: self.chan,
+            waiter: Waiter::new(),
+        };
+
+        match reserve.await {
+            // Nothing can take the space before the message is pushed
+            Ok(()) => self.chan.try_send(message),
+            Err(_) => Err(SendError::Closed(message)),
+        }
+    }
+
+    /// Send a message if there is space in the channel
+    pub fn try_send(&self, message: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(message)
+    }
+
+    /// Whether every receiver is gone or the channel was closed
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
+    }
+}
+
+impl<'ch, T, const N: usize, const W: usize> Clone for Sender<'ch, T, N, W> {
+    fn clone(&self) -> Self {
+        Sender::new(self.chan)
+    }
+}
+
+impl<'ch, T, const N: usize, const W: usize> Drop for Sender<'ch, T, N, W> {
+    fn drop(&mut self) {
+        defmt::trace!("Dropping sender");
+        let mut inner = self.chan.inner.borrow_mut();
+        inner.tx_count -= 1;
+        if inner.tx_count == 0 {
+            drop(inner);
+            self.chan.close();
+        }
+    }
+}
+
+// ===== impl Receiver =====
+
+impl<'ch, T, const N: usize, const W: usize> Receiver<'ch, T, N, W> {
+    fn new(chan: &'ch Channel<T, N, W>) -> Receiver<'ch, T, N, W> {
+        chan.inner.borrow_mut().rx_count += 1;
+        Receiver { chan }
+    }
+
+    /// Wait for a message. Returns `None` once the channel is closed and
+    /// empty
+    pub fn recv(&self) -> Recv<'ch, T, N, W> {
+        Recv {
+            chan: self.chan,
+            waiter: Waiter::new(),
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Stop accepting new messages. Messages already in the channel can
+    /// still be received, after which `recv` returns `None`
+    pub fn close(&self) {
+        self.chan.close();
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
+    }
+}
+
+impl<'ch, T, const N: usize, const W: usize> Clone for Receiver<'ch, T, N, W> {
+    fn clone(&self) -> Self {
+        Receiver::new(self.chan)
+    }
+}
+
+impl<'ch, T, const N: usize, const W: usize> Drop for Receiver<'ch, T, N, W> {
+    fn drop(&mut self) {
+        defmt::trace!("Dropping receiver");
+        let mut inner = self.chan.inner.borrow_mut();
+        inner.rx_count -= 1;
+        if inner.rx_count == 0 {
+            drop(inner);
+            self.chan.close();
+        }
+    }
+}
+
+// ===== impl Recv =====
+
+impl<'a, T, const N: usize, const W: usize> Recv<'a, T, N, W> {
+    fn waiter(self: Pin<&Self>) -> Pin<&Waiter> {
+        // Safe since the waiter is pinned along with the future
+        unsafe { self.map_unchecked(|recv| &recv.waiter) }
+    }
+}
+
+impl<'a, T, const N: usize, const W: usize> Future for Recv<'a, T, N, W> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref();
+        let waiter = this.waiter();
+        coop::poll_budgeted(cx, |cx| {
+            let mut inner = this.chan.inner.borrow_mut();
+            // Wait behind the receivers already in line
+            let turn = match waiter.is_idle() {
+                true => !inner.receivers.has_waiters(),
+                false => waiter.is_notified(),
+            };
+            let message = match turn {
+                true => inner.queue.pop_front(),
+                false => None,
+            };
+
+            match message {
+                Some(message) => {
+                    unsafe { inner.receivers.remove(waiter) };
+                    inner.senders.notify_one();
+                    // Another message may have arrived while this receiver
+                    // was waiting to run. Once a closed channel is drained,
+                    // everyone still in line gets `None`
+                    if !inner.queue.is_empty() {
+                        inner.receivers.notify_one();
+                    } else if inner.closed {
+                        inner.receivers.notify_all();
+                    }
+                    Poll::Ready(Some(message))
+                }
+                None if inner.closed && inner.queue.is_empty() => {
+                    unsafe { inner.receivers.remove(waiter) };
+                    Poll::Ready(None)
+                }
+                None => {
+                    unsafe { inner.receivers.wait(waiter, cx.waker()) };
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+impl<'a, T, const N: usize, const W: usize> Drop for Recv<'a, T, N, W> {
+    fn drop(&mut self) {
+        // Safe since the future is not moved again
+        let this = unsafe { Pin::new_unchecked(&*self) };
+        let mut inner = self.chan.inner.borrow_mut();
+        // Pass on a wakeup this receiver won't act on
+        if unsafe { inner.receivers.remove(this.waiter()) } {
+            inner.receivers.notify_one();
+        }
+    }
+}
+
+// ===== impl Reserve =====
+
+impl<'a, T, const N: usize, const W: usize> Reserve<'a, T, N, W> {
+    fn waiter(self: Pin<&Self>) -> Pin<&Waiter> {
+        // Safe since the waiter is pinned along with the future
+        unsafe { self.map_unchecked(|reserve| &reserve.waiter) }
+    }
+}
+
+impl<'a, T, const N: usize, const W: usize> Future for Reserve<'a, T, N, W> {
+    type Output = Result<(), SendError<()>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref();
+        let waiter = this.waiter();
+        coop::poll_budgeted(cx, |cx| {
+            let mut inner = this.chan.inner.borrow_mut();
+            if inner.closed {
+                unsafe { inner.senders.remove(waiter) };
+                return Poll::Ready(Err(SendError::Closed(())));
+            }
+
+            // Wait behind the senders already in line
+            let turn = match waiter.is_idle() {
+                true => !inner.senders.has_waiters(),
+                false => waiter.is_notified(),
+            };
+            if turn && inner.queue.len() < N {
+                unsafe { inner.senders.remove(waiter) };
+                // Let the next sender in line know if there is space left
+                // once this one's message is in
+                if inner.queue.len() + 1 < N {
+                    inner.senders.notify_one();
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            unsafe { inner.senders.wait(waiter, cx.waker()) };
+            Poll::Pending
+        })
+    }
+}
+
+impl<'a, T, const N: usize, const W: usize> Drop for Reserve<'a, T, N, W> {
+    fn drop(&mut self) {
+        // Safe since the future is not moved again
+        let this = unsafe { Pin::new_unchecked(&*self) };
+        let mut inner = self.chan.inner.borrow_mut();
+        // Pass on a wakeup this sender won't act on
+        if unsafe { inner.senders.remove(this.waiter()) } {
+            inner.senders.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::poll;
+    use core::cell::Cell;
+    use std::boxed::Box;
+
+    fn reserve<T, const N: usize, const W: usize>(
+        chan: &Channel<T, N, W>,
+    ) -> Pin<Box<Reserve<'_, T, N, W>>> {
+        Box::pin(Reserve {
+            chan,
+            waiter: Waiter::new(),
+        })
+    }
+
+    #[test]
+    fn receivers_served_in_order() {
+        let chan: Channel<u32, 4, 4> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = [Cell::new(false), Cell::new(false), Cell::new(false)];
+
+        let mut first = Box::pin(rx.recv());
+        let mut second = Box::pin(rx.recv());
+        assert!(poll(&mut first, &woken[0]).is_pending());
+        assert!(poll(&mut second, &woken[1]).is_pending());
+
+        tx.try_send(1).unwrap();
+        assert!(woken[0].get());
+        assert!(!woken[1].get());
+
+        // A new receiver does not take the message from the one woken for it
+        let mut third = Box::pin(rx.recv());
+        assert!(poll(&mut third, &woken[2]).is_pending());
+        assert_eq!(poll(&mut first, &woken[0]), Poll::Ready(Some(1)));
+
+        tx.try_send(2).unwrap();
+        assert!(woken[1].get());
+        assert!(!woken[2].get());
+        assert_eq!(poll(&mut second, &woken[1]), Poll::Ready(Some(2)));
+
+        tx.try_send(3).unwrap();
+        assert!(woken[2].get());
+        assert_eq!(poll(&mut third, &woken[2]), Poll::Ready(Some(3)));
+    }
+
+    #[test]
+    fn senders_served_in_order() {
+        let chan: Channel<u32, 1, 4> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = [Cell::new(false), Cell::new(false), Cell::new(false)];
+        tx.try_send(0).unwrap();
+
+        let mut first = reserve(&chan);
+        let mut second = reserve(&chan);
+        assert!(poll(&mut first, &woken[0]).is_pending());
+        assert!(poll(&mut second, &woken[1]).is_pending());
+
+        assert!(matches!(rx.try_recv(), Ok(0)));
+        assert!(woken[0].get());
+        assert!(!woken[1].get());
+
+        // There is space, but it is the first sender's turn
+        let mut third = reserve(&chan);
+        assert!(poll(&mut third, &woken[2]).is_pending());
+        assert!(matches!(poll(&mut first, &woken[0]), Poll::Ready(Ok(()))));
+        tx.try_send(1).unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok(1)));
+        assert!(woken[1].get());
+        assert!(!woken[2].get());
+        assert!(matches!(poll(&mut second, &woken[1]), Poll::Ready(Ok(()))));
+    }
+
+    #[test]
+    fn dropped_receiver_passes_on_wakeup() {
+        let chan: Channel<u32, 4, 4> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = [Cell::new(false), Cell::new(false)];
+
+        let mut first = Box::pin(rx.recv());
+        let mut second = Box::pin(rx.recv());
+        assert!(poll(&mut first, &woken[0]).is_pending());
+        assert!(poll(&mut second, &woken[1]).is_pending());
+
+        tx.try_send(1).unwrap();
+        drop(first);
+        assert!(woken[1].get());
+        assert_eq!(poll(&mut second, &woken[1]), Poll::Ready(Some(1)));
+    }
+
+    #[test]
+    fn close_drains_then_ends() {
+        let chan: Channel<u32, 4, 4> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = [Cell::new(false), Cell::new(false)];
+
+        let mut waiting = Box::pin(rx.recv());
+        assert!(poll(&mut waiting, &woken[0]).is_pending());
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        rx.close();
+        assert!(woken[0].get());
+
+        // Closed, so senders give up right away
+        assert!(matches!(
+            poll(&mut reserve(&chan), &woken[1]),
+            Poll::Ready(Err(SendError::Closed(())))
+        ));
+        assert!(matches!(tx.try_send(3), Err(SendError::Closed(3))));
+
+        let mut late = Box::pin(rx.recv());
+        assert!(poll(&mut late, &woken[1]).is_pending());
+        assert_eq!(poll(&mut waiting, &woken[0]), Poll::Ready(Some(1)));
+        // The receiver behind is woken for the last message
+        assert!(woken[1].get());
+        assert_eq!(poll(&mut late, &woken[1]), Poll::Ready(Some(2)));
+        assert_eq!(poll(&mut Box::pin(rx.recv()), &woken[0]), Poll::Ready(None));
+    }
+}
@@ -1,10 +1,15 @@
 //! A bounded multi-producer, single-consumer queue for sending values between
 //! asynchronous tasks.
 
-use core::future::poll_fn;
+use core::future::{poll_fn, Future};
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll};
 
 use super::channel::Channel;
+use crate::task::coop;
 use crate::channel::error::{SendError, TryRecvError};
+use crate::sync::wait_queue::Waiter;
 
 pub const fn channel<T, const N: usize>() -> Channel<T, N> {
     Channel::new()
@@ -14,7 +19,7 @@ pub const fn channel<T, const N: usize>() -> Channel<T, N> {
 /// half contains a reference to the channel. This avoids having to use
 /// reference counting explicitly which requires allocations
 pub fn split<T, const N: usize>(chan: &Channel<T, N>) -> (Sender<T, N>, Receiver<T, N>) {
-    (Sender { chan }, Receiver { chan })
+    (Sender::new(chan), Receiver { chan })
 }
 
 pub struct Sender<'ch, T, const N: usize> {
@@ -25,28 +30,133 @@ pub struct Receiver<'ch, T, const N: usize> {
     chan: &'ch Channel<T, N>,
 }
 
+/// A slot reserved in the channel with [`Sender::reserve`]. Sending through
+/// it only fails if the channel was closed since. The slot is given back if
+/// the permit is dropped
+pub struct Permit<'ch, T, const N: usize> {
+    chan: &'ch Channel<T, N>,
+}
+
+/// Future waiting for a slot in the channel
+struct Reserve<'ch, T, const N: usize> {
+    chan: &'ch Channel<T, N>,
+    /// Place in the channel's sender wait list. It only waits there, and
+    /// leaves when the future is dropped
+    waiter: Waiter,
+}
+
 // ==== impl Sender =====
 
 impl<'ch, T, const N: usize> Sender<'ch, T, N> {
-    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
-        self.chan.send(message)
+    fn new(chan: &'ch Channel<T, N>) -> Sender<'ch, T, N> {
+        chan.add_sender();
+        Sender { chan }
+    }
+
+    /// Send a message, waiting for space if the channel is full. Fails if
+    /// the channel is closed
+    pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+        match self.reserve().await {
+            Ok(permit) => permit.send(message),
+            Err(_) => Err(SendError::Closed(message)),
+        }
+    }
+
+    /// Send a message if there is space in the channel
+    pub fn try_send(&self, message: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(message)
+    }
+
+    /// Wait for space in the channel and reserve it. Senders waiting for
+    /// space are served in the order they started waiting
+    pub async fn reserve(&self) -> Result<Permit<'ch, T, N>, SendError<()>> {
+        let reserve = Reserve {
+            chan: self.chan,
+            waiter: Waiter::new(),
+        };
+        reserve.await?;
+        Ok(Permit { chan: self.chan })
+    }
+
+    /// Wait until the receiver is closed or dropped
+    pub async fn closed(&self) {
+        poll_fn(|cx| self.chan.poll_closed(cx)).await
+    }
+
+    /// Whether the receiver is closed or dropped. Sending fails once it is
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
     }
 }
 
 impl<'ch, T, const N: usize> Clone for Sender<'ch, T, N> {
     fn clone(&self) -> Self {
-        self.chan.incr_tx_count();
-        Self { chan: self.chan }
+        Sender::new(self.chan)
     }
 }
 
 impl<'ch, T, const N: usize> Drop for Sender<'ch, T, N> {
     fn drop(&mut self) {
         defmt::trace!("Dropping sender");
-        self.chan.decr_tx_count();
-        if self.chan.tx_count() == 0 {
-            self.chan.close();
-        }
+        self.chan.remove_sender();
+    }
+}
+
+// ===== impl Permit =====
+
+impl<'ch, T, const N: usize> Permit<'ch, T, N> {
+    /// Send a message into the reserved slot. The message is handed back if
+    /// the channel was closed since the slot was reserved
+    pub fn send(self, message: T) -> Result<(), SendError<T>> {
+        let res = self.chan.send_reserved(message);
+        mem::forget(self);
+        res
+    }
+}
+
+impl<'ch, T, const N: usize> Drop for Permit<'ch, T, N> {
+    fn drop(&mut self) {
+        self.chan.release_reserved();
+    }
+}
+
+// ===== impl Reserve =====
+
+impl<'ch, T, const N: usize> Reserve<'ch, T, N> {
+    fn waiter(self: Pin<&Self>) -> Pin<&Waiter> {
+        // Safe since the waiter is pinned along with the future
+        unsafe { self.map_unchecked(|reserve| &reserve.waiter) }
+    }
+}
+
+impl<'ch, T, const N: usize> Future for Reserve<'ch, T, N> {
+    type Output = Result<(), SendError<()>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref();
+        coop::poll_budgeted(cx, |cx| unsafe { this.chan.poll_reserve(cx, this.waiter()) })
+    }
+}
+
+impl<'ch, T, const N: usize> Drop for Reserve<'ch, T, N> {
+    fn drop(&mut self) {
+        // Safe since the future is not moved again
+        let this = unsafe { Pin::new_unchecked(&*self) };
+        unsafe { self.chan.remove_tx_waiter(this.waiter()) };
     }
 }
 
@@ -54,12 +164,32 @@ impl<'ch, T, const N: usize> Drop for Sender<'ch, T, N> {
 
 impl<'ch, T, const N: usize> Receiver<'ch, T, N> {
     pub async fn recv(&self) -> Option<T> {
-        poll_fn(|cx| self.chan.poll_recv(cx)).await
+        poll_fn(|cx| coop::poll_budgeted(cx, |cx| self.chan.poll_recv(cx))).await
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         self.chan.try_recv()
     }
+
+    /// Stop accepting new messages. Messages already in the channel can
+    /// still be received, after which `recv` returns `None`
+    pub fn close(&self) {
+        self.chan.close();
+    }
+
+    /// Number of messages in the channel
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chan.is_empty()
+    }
+
+    /// Maximum number of messages the channel holds
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
+    }
 }
 
 impl<'ch, T, const N: usize> Drop for Receiver<'ch, T, N> {
@@ -68,3 +198,58 @@ impl<'ch, T, const N: usize> Drop for Receiver<'ch, T, N> {
         self.chan.close();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::poll;
+    use core::cell::Cell;
+    use std::boxed::Box;
+
+    fn reserve<T, const N: usize>(chan: &Channel<T, N>) -> Pin<Box<Reserve<'_, T, N>>> {
+        Box::pin(Reserve {
+            chan,
+            waiter: Waiter::new(),
+        })
+    }
+
+    #[test]
+    fn one_sender_woken_per_slot() {
+        let chan: Channel<u32, 1> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = [Cell::new(false), Cell::new(false)];
+        tx.try_send(0).unwrap();
+
+        let mut first = reserve(&chan);
+        let mut second = reserve(&chan);
+        assert!(poll(&mut first, &woken[0]).is_pending());
+        assert!(poll(&mut second, &woken[1]).is_pending());
+
+        assert!(matches!(rx.try_recv(), Ok(0)));
+        assert!(woken[0].get());
+        assert!(!woken[1].get());
+
+        // The first sender gives up, so the slot goes to the second
+        drop(first);
+        assert!(woken[1].get());
+        assert!(matches!(poll(&mut second, &woken[1]), Poll::Ready(Ok(()))));
+    }
+
+    #[test]
+    fn permit_hands_back_message_once_closed() {
+        let chan: Channel<u32, 1> = Channel::new();
+        let (tx, rx) = split(&chan);
+        let woken = Cell::new(false);
+
+        assert!(matches!(poll(&mut reserve(&chan), &woken), Poll::Ready(Ok(()))));
+        let permit = Permit { chan: &chan };
+        rx.close();
+        assert!(matches!(permit.send(1), Err(SendError::Closed(1))));
+
+        // The slot is free again but the channel stays closed
+        assert!(matches!(tx.try_send(2), Err(SendError::Closed(2))));
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+    }
+}
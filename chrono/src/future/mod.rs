@@ -0,0 +1,7 @@
+mod select;
+pub use select::{select, select_array, Either, Select, SelectArray};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use super::select::random;
+}
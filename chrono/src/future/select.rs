@@ -0,0 +1,114 @@
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Output of [`select`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Future returned by [`select`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select<A, B> {
+    a: A,
+    b: B,
+}
+
+/// Future returned by [`select_array`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SelectArray<F, const N: usize> {
+    futures: [F; N],
+}
+
+/// State of the generator used by fair `select!`s to pick the first branch
+struct Seed(Cell<u32>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Seed {}
+
+static SEED: Seed = Seed(Cell::new(0x9E37_79B9));
+
+/// Wait for the first of two futures to complete. `a` is polled first. The
+/// other future is dropped
+pub fn select<A, B>(a: A, b: B) -> Select<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    Select { a, b }
+}
+
+/// Wait for the first of `N` futures to complete, returning its output and
+/// index. Futures are polled in order. The others are dropped
+pub fn select_array<F: Future, const N: usize>(futures: [F; N]) -> SelectArray<F, N> {
+    SelectArray { futures }
+}
+
+/// Pseudo-random number in `0..n`, used by `select!` to pick which branch
+/// is polled first
+pub fn random(n: usize) -> usize {
+    // xorshift32
+    let mut x = SEED.0.get();
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    SEED.0.set(x);
+    x as usize % n
+}
+
+// ===== impl Either =====
+
+impl<T> Either<T, T> {
+    /// Get the output when both futures have the same output type
+    pub fn into_inner(self) -> T {
+        match self {
+            Either::Left(t) | Either::Right(t) => t,
+        }
+    }
+}
+
+// ===== impl Select =====
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the futures are never moved out of `self`
+        let this = unsafe { self.get_unchecked_mut() };
+
+        let a = unsafe { Pin::new_unchecked(&mut this.a) };
+        if let Poll::Ready(out) = a.poll(cx) {
+            return Poll::Ready(Either::Left(out));
+        }
+
+        let b = unsafe { Pin::new_unchecked(&mut this.b) };
+        if let Poll::Ready(out) = b.poll(cx) {
+            return Poll::Ready(Either::Right(out));
+        }
+
+        Poll::Pending
+    }
+}
+
+// ===== impl SelectArray =====
+
+impl<F: Future, const N: usize> Future for SelectArray<F, N> {
+    type Output = (F::Output, usize);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the futures are never moved out of `self`
+        let this = unsafe { self.get_unchecked_mut() };
+
+        for (idx, future) in this.futures.iter_mut().enumerate() {
+            let future = unsafe { Pin::new_unchecked(future) };
+            if let Poll::Ready(out) = future.poll(cx) {
+                return Poll::Ready((out, idx));
+            }
+        }
+
+        Poll::Pending
+    }
+}
@@ -1,30 +1,51 @@
 #![no_std]
 #![feature(type_alias_impl_trait, waker_getters)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod channel;
-pub use channel::mpsc;
+pub use channel::{mpmc, mpsc};
 
 pub mod io {
     pub use embedded_io::asynch::{Read as AsyncRead, Write as AsyncWrite};
 }
 
+pub mod future;
+
 mod init;
 pub use init::init;
 
+#[cfg(feature = "log-task")]
+mod log;
+
 pub mod net;
 
 pub mod runtime;
 pub use runtime::Runtime;
 
+pub mod sync;
+
 pub mod task;
+
+#[cfg(test)]
+mod test_util;
 pub use task::spawn;
+#[cfg(feature = "alloc")]
+pub use task::spawn_boxed;
 pub use task::Task;
 
 pub mod time;
 
+#[cfg(feature = "trace")]
+pub mod trace;
+
+pub mod watchdog;
+
 // Re-exports
 pub use chrono_macros::alloc;
 pub use chrono_macros::main;
+pub use chrono_macros::select;
 
 pub use futures_util::join;
 pub use futures_util::pin_mut as pin;
@@ -0,0 +1,23 @@
+//! Tags every defmt message with the task that logged it.
+//!
+//! Enabling the `log-task` feature installs the defmt timestamp, so it
+//! cannot be combined with another `defmt::timestamp!` in the application.
+//! Each message is prefixed with the time in microseconds and either
+//! `<id>:<name>` of the running task or `main` outside of a task.
+
+use crate::task;
+use crate::time::Instant;
+
+/// The task tag printed in front of every message
+struct Tag;
+
+impl defmt::Format for Tag {
+    fn format(&self, f: defmt::Formatter) {
+        match task::current() {
+            Some(current) => defmt::write!(f, "[{}]", current),
+            None => defmt::write!(f, "[main]"),
+        }
+    }
+}
+
+defmt::timestamp!("{=u32:us} {}", Instant::now().ticks(), Tag);
@@ -10,6 +10,7 @@ use smoltcp::wire::IpEndpoint;
 
 use crate::io::{AsyncRead, AsyncWrite};
 use crate::net;
+use crate::task::coop;
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, defmt::Format)]
 pub enum Error {
@@ -161,7 +162,7 @@ impl AsyncRead for TcpSocket {
         Self: 'a;
 
     fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> Self::ReadFuture<'a> {
-        poll_fn(|cx| self.poll_read(cx, buf))
+        poll_fn(|cx| coop::poll_budgeted(cx, |cx| self.poll_read(cx, buf)))
     }
 }
 
@@ -171,7 +172,7 @@ impl AsyncWrite for TcpSocket {
         Self: 'a;
 
     fn write<'a>(&'a mut self, buf: &'a [u8]) -> Self::WriteFuture<'a> {
-        poll_fn(|cx| self.poll_write(cx, buf))
+        poll_fn(|cx| coop::poll_budgeted(cx, |cx| self.poll_write(cx, buf)))
     }
 
     type FlushFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a
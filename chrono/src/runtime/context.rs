@@ -1,11 +1,15 @@
 use core::cell::Cell;
+use core::ptr::NonNull;
 
 use super::runtime::Handle;
 use super::runtime::Spawner;
+use crate::task::Task;
 use crate::time::{self, Driver};
 
 static CONTEXT: Context = Context::new();
 
+static CURRENT: Current = Current::new();
+
 #[derive(Clone)]
 pub(crate) struct Context(Cell<Option<Handle>>);
 
@@ -17,10 +21,13 @@ impl Context {
         Context(Cell::new(None))
     }
 
-    fn spawner(&self) -> Spawner {
+    fn handle(&self) -> Handle {
         let inner = self.0.get();
-        let handle = inner.as_ref().expect("No reactor running");
-        handle.spawner
+        inner.expect("No reactor running")
+    }
+
+    fn spawner(&self) -> Spawner {
+        self.handle().spawner
     }
 
     fn with<F, R>(&self, f: F) -> R
@@ -31,6 +38,18 @@ impl Context {
     }
 }
 
+/// The task currently being polled
+struct Current(Cell<Option<NonNull<Task>>>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Current {}
+
+impl Current {
+    const fn new() -> Current {
+        Current(Cell::new(None))
+    }
+}
+
 pub(crate) struct EnterGuard;
 
 impl Drop for EnterGuard {
@@ -49,12 +68,37 @@ pub(super) fn enter(new: Handle) -> EnterGuard {
     EnterGuard {}
 }
 
+/// Restores the previously running task when dropped
+pub(crate) struct TaskGuard(Option<NonNull<Task>>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        CURRENT.0.replace(self.0);
+    }
+}
+
+/// Sets `task` as the task currently being polled. Returns a [`TaskGuard`]
+/// which restores the previous task when dropped
+pub(super) fn enter_task(task: NonNull<Task>) -> TaskGuard {
+    TaskGuard(CURRENT.0.replace(Some(task)))
+}
+
 // ===== Functions for retrieving handles =====
 
+/// Get the [`Handle`] of the runtime currently running
+pub fn handle() -> Handle {
+    CONTEXT.handle()
+}
+
 pub(crate) fn spawner() -> Spawner {
     CONTEXT.spawner()
 }
 
 pub(crate) fn time_driver() -> &'static mut Driver {
     time::driver()
-}
\ No newline at end of file
+}
+
+/// The task currently being polled, if any
+pub(crate) fn current_task() -> Option<NonNull<Task>> {
+    CURRENT.0.get()
+}
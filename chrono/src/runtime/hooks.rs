@@ -0,0 +1,78 @@
+#[cfg(feature = "hooks")]
+use core::cell::Cell;
+
+use crate::task::TaskInfo;
+
+/// Callbacks invoked by the runtime at every task lifecycle transition. Every
+/// method defaults to doing nothing so implementors only override what they
+/// need.
+///
+/// Hooks are only called when the `hooks` feature is enabled. Without it, the
+/// calls compile away entirely
+pub trait RuntimeHooks {
+    /// A task was spawned onto the runtime
+    fn on_spawn(&self, _task: &TaskInfo) {}
+
+    /// A task was woken and scheduled to run
+    fn on_wake(&self, _task: &TaskInfo) {}
+
+    /// A task is about to be polled
+    fn on_poll(&self, _task: &TaskInfo) {}
+
+    /// A task was polled and is pending
+    fn on_idle(&self, _task: &TaskInfo) {}
+
+    /// A task was polled and is complete
+    fn on_complete(&self, _task: &TaskInfo) {}
+
+    /// A task was aborted and will not be polled again
+    fn on_abort(&self, _task: &TaskInfo) {}
+
+    /// The timer of a task fired
+    fn on_timer(&self, _task: &TaskInfo) {}
+
+    /// A task finished polling after its deadline
+    fn on_deadline_miss(&self, _task: &TaskInfo) {}
+
+    /// The runtime has no tasks to run and is about to wait for an event
+    fn on_park(&self) {}
+
+    /// The runtime woke up after waiting for an event
+    fn on_unpark(&self) {}
+}
+
+#[cfg(feature = "hooks")]
+static HOOKS: Hooks = Hooks::new();
+
+#[cfg(feature = "hooks")]
+struct Hooks(Cell<Option<&'static dyn RuntimeHooks>>);
+
+// Safe since we are in a single-threaded environment
+#[cfg(feature = "hooks")]
+unsafe impl Sync for Hooks {}
+
+#[cfg(feature = "hooks")]
+impl Hooks {
+    const fn new() -> Hooks {
+        Hooks(Cell::new(None))
+    }
+}
+
+/// Install the hooks called by the runtime
+#[cfg(feature = "hooks")]
+pub fn set_hooks(hooks: &'static dyn RuntimeHooks) {
+    HOOKS.0.replace(Some(hooks));
+}
+
+/// Call the installed hooks, if any
+#[inline(always)]
+#[allow(unused_variables)]
+pub(crate) fn emit<F>(f: F)
+where
+    F: FnOnce(&dyn RuntimeHooks),
+{
+    #[cfg(feature = "hooks")]
+    if let Some(hooks) = HOOKS.0.get() {
+        f(hooks)
+    }
+}
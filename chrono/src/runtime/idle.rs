@@ -0,0 +1,260 @@
+use core::cell::Cell;
+
+use crate::time::{Duration, Instant};
+
+static ON_EXIT: Flag = Flag(Cell::new(false));
+
+/// How the core waits for an event when the runtime has no tasks to run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum SleepMode {
+    /// Wait for an event. Wakes on interrupts and on `sev`
+    Wfe,
+    /// Wait for an interrupt
+    Wfi,
+    /// Wait for an interrupt and go straight back to sleep after the interrupt
+    /// handler returns, unless the handler woke a task. Interrupts that should
+    /// resume the runtime without waking a task must call [`resume`]
+    SleepOnExit,
+    /// Enter Stop mode. Clocks are stopped so neither the time driver nor the
+    /// cycle counter behind [`Instant`] run. The policy has to arm a wakeup
+    /// source, such as the RTC, in [`IdlePolicy::before_sleep`] and report
+    /// how long the core slept from [`IdlePolicy::after_sleep`]. Only entered
+    /// when there is a timer deadline to wake up for
+    Stop,
+}
+
+/// Decides how the runtime sleeps when it is idle. The runtime calls
+/// [`decide`](IdlePolicy::decide) every time the task queue is empty
+pub trait IdlePolicy {
+    /// Choose how to sleep given the time until the next timer deadline. It
+    /// is `None` if there are no timers
+    fn decide(&self, until_deadline: Option<Duration>) -> SleepMode;
+
+    /// Time it takes the core to wake up from `mode`. The time driver is
+    /// started this much earlier so the deadline is not missed
+    fn wakeup_latency(&self, _mode: SleepMode) -> Duration {
+        Duration::default()
+    }
+
+    /// Called right before the core goes to sleep. `wake_at` is when the core
+    /// has to be awake by, with the wakeup latency already taken off. It is
+    /// `None` if there are no timers
+    fn before_sleep(&self, _mode: SleepMode, _wake_at: Option<Instant>) {}
+
+    /// Called right after the core wakes up. Returns how long the cycle
+    /// counter was stopped for, which is only the case in Stop mode. The
+    /// runtime's clock is moved forward by this much so timers, task stats
+    /// and metrics account for the time asleep
+    fn after_sleep(&self, _mode: SleepMode) -> Duration {
+        Duration::default()
+    }
+}
+
+/// Always wait for an event. This is the default policy
+pub struct WaitForEvent;
+
+/// Enter Stop mode when the next deadline is far enough away to make up for
+/// the wakeup latency. Otherwise wait for an event
+pub struct LowPower {
+    /// The minimum time to the next deadline before entering Stop mode
+    pub stop_threshold: Duration,
+    /// Time it takes to wake up from Stop mode
+    pub stop_latency: Duration,
+}
+
+/// What the runtime does when it is idle
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Plan {
+    pub mode: SleepMode,
+    /// Duration to start the time driver with, if any
+    pub timer: Option<Duration>,
+    /// When the core has to be awake by, if there is a deadline
+    pub wake_at: Option<Instant>,
+}
+
+struct Flag(Cell<bool>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Flag {}
+
+// ===== impl WaitForEvent =====
+
+impl IdlePolicy for WaitForEvent {
+    fn decide(&self, _: Option<Duration>) -> SleepMode {
+        SleepMode::Wfe
+    }
+}
+
+// ===== impl LowPower =====
+
+impl IdlePolicy for LowPower {
+    fn decide(&self, until_deadline: Option<Duration>) -> SleepMode {
+        match until_deadline {
+            // Nothing would wake the core up from Stop mode
+            None => SleepMode::Wfe,
+            Some(dur) if dur >= self.stop_threshold + self.stop_latency => SleepMode::Stop,
+            Some(_) => SleepMode::Wfe,
+        }
+    }
+
+    fn wakeup_latency(&self, mode: SleepMode) -> Duration {
+        match mode {
+            SleepMode::Stop => self.stop_latency,
+            _ => Duration::default(),
+        }
+    }
+}
+
+// ===== Functions =====
+
+/// Work out how to sleep and when the time driver should fire
+pub(crate) fn plan(
+    policy: &dyn IdlePolicy,
+    now: Instant,
+    until_deadline: Option<Duration>,
+) -> Plan {
+    let mode = match policy.decide(until_deadline) {
+        SleepMode::Stop if until_deadline.is_none() => {
+            defmt::debug!("No deadline to wake up from Stop mode for. Waiting for an event");
+            SleepMode::Wfe
+        }
+        mode => mode,
+    };
+
+    let latency = policy.wakeup_latency(mode);
+    let until_wake = until_deadline.map(|dur| dur.saturating_sub(latency));
+    let timer = match mode {
+        // The time driver is stopped along with the clocks. The policy arms
+        // its own wakeup source
+        SleepMode::Stop => None,
+        _ => until_wake,
+    };
+
+    Plan {
+        mode,
+        timer,
+        wake_at: until_wake.map(|dur| now + dur),
+    }
+}
+
+/// Put the core to sleep until it is woken up
+pub(crate) fn sleep(mode: SleepMode) {
+    // Safe since we only touch the sleep bits of the SCB
+    let mut core = unsafe { cortex_m::Peripherals::steal() };
+
+    match mode {
+        SleepMode::Wfe => cortex_m::asm::wfe(),
+        SleepMode::Wfi => cortex_m::asm::wfi(),
+        SleepMode::SleepOnExit => {
+            ON_EXIT.0.set(true);
+            core.SCB.set_sleeponexit();
+            cortex_m::asm::wfi();
+            core.SCB.clear_sleeponexit();
+            ON_EXIT.0.set(false);
+        }
+        SleepMode::Stop => {
+            core.SCB.set_sleepdeep();
+            cortex_m::asm::wfi();
+            core.SCB.clear_sleepdeep();
+        }
+    }
+}
+
+/// Resume the runtime after the current interrupt handler returns when it
+/// is sleeping in [`SleepMode::SleepOnExit`]. Waking a task does this
+pub fn resume() {
+    if ON_EXIT.0.replace(false) {
+        let mut core = unsafe { cortex_m::Peripherals::steal() };
+        core.SCB.clear_sleeponexit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Mock {
+        mode: SleepMode,
+        latency: Duration,
+    }
+
+    impl IdlePolicy for Mock {
+        fn decide(&self, _: Option<Duration>) -> SleepMode {
+            self.mode
+        }
+
+        fn wakeup_latency(&self, _: SleepMode) -> Duration {
+            self.latency
+        }
+    }
+
+    #[test]
+    fn plan_compensates_wakeup_latency() {
+        let policy = Mock {
+            mode: SleepMode::Stop,
+            latency: Duration::from_millis(2),
+        };
+
+        let now = Instant::from_millis(100);
+        let plan = plan(&policy, now, Some(Duration::from_millis(10)));
+        assert_eq!(plan.mode, SleepMode::Stop);
+        assert_eq!(plan.wake_at, Some(Instant::from_millis(108)));
+        // The time driver doesn't run in Stop mode
+        assert_eq!(plan.timer, None);
+    }
+
+    #[test]
+    fn plan_never_stops_without_deadline() {
+        let policy = Mock {
+            mode: SleepMode::Stop,
+            latency: Duration::from_millis(2),
+        };
+
+        let plan = plan(&policy, Instant::from_millis(0), None);
+        assert_eq!(plan.mode, SleepMode::Wfe);
+        assert_eq!(plan.wake_at, None);
+    }
+
+    #[test]
+    fn plan_without_deadline_has_no_timer() {
+        let policy = Mock {
+            mode: SleepMode::Wfi,
+            latency: Duration::from_millis(2),
+        };
+
+        assert_eq!(plan(&policy, Instant::from_millis(0), None).timer, None);
+    }
+
+    #[test]
+    fn plan_latency_longer_than_deadline() {
+        let policy = Mock {
+            mode: SleepMode::Wfi,
+            latency: Duration::from_millis(5),
+        };
+
+        let plan = plan(
+            &policy,
+            Instant::from_millis(0),
+            Some(Duration::from_millis(1)),
+        );
+        assert_eq!(plan.timer, Some(Duration::default()));
+    }
+
+    #[test]
+    fn low_power_stops_for_distant_deadlines() {
+        let policy = LowPower {
+            stop_threshold: Duration::from_millis(10),
+            stop_latency: Duration::from_millis(1),
+        };
+
+        assert_eq!(policy.decide(None), SleepMode::Wfe);
+        assert_eq!(
+            policy.decide(Some(Duration::from_millis(5))),
+            SleepMode::Wfe
+        );
+        assert_eq!(
+            policy.decide(Some(Duration::from_millis(11))),
+            SleepMode::Stop
+        );
+    }
+}
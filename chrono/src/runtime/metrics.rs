@@ -0,0 +1,107 @@
+use core::cell::Cell;
+
+use crate::time::Duration;
+
+/// Counters maintained by the runtime as it executes. They saturate rather
+/// than wrap, so a snapshot never goes backwards
+pub(crate) struct Metrics {
+    /// Time spent running tasks, in microseconds
+    busy: Cell<u64>,
+    /// Time spent waiting for an event, in microseconds
+    idle: Cell<u64>,
+    wakeups: Cell<u32>,
+    generations: Cell<u32>,
+    timers_fired: Cell<u32>,
+    spawns: Cell<u32>,
+}
+
+/// A snapshot of the runtime's metrics
+#[derive(Clone, Copy, defmt::Format)]
+pub struct RuntimeMetrics {
+    /// Time spent running tasks, in microseconds
+    pub busy_us: u64,
+    /// Time spent waiting for an event, in microseconds
+    pub idle_us: u64,
+    /// Number of times a task was woken
+    pub wakeups: u32,
+    /// Number of generations of the task queue processed
+    pub generations: u32,
+    /// Number of timers that have fired
+    pub timers_fired: u32,
+    /// Number of tasks spawned
+    pub spawns: u32,
+}
+
+// ===== impl Metrics =====
+
+impl Metrics {
+    pub const fn new() -> Metrics {
+        Metrics {
+            busy: Cell::new(0),
+            idle: Cell::new(0),
+            wakeups: Cell::new(0),
+            generations: Cell::new(0),
+            timers_fired: Cell::new(0),
+            spawns: Cell::new(0),
+        }
+    }
+
+    pub fn record_busy(&self, elapsed: Duration) {
+        self.busy.set(self.busy.get().saturating_add(elapsed.as_micros() as u64));
+    }
+
+    pub fn record_idle(&self, elapsed: Duration) {
+        self.idle.set(self.idle.get().saturating_add(elapsed.as_micros() as u64));
+    }
+
+    pub fn incr_wakeups(&self) {
+        self.wakeups.set(self.wakeups.get().saturating_add(1));
+    }
+
+    pub fn incr_generations(&self) {
+        self.generations.set(self.generations.get().saturating_add(1));
+    }
+
+    pub fn incr_timers_fired(&self, n: u32) {
+        self.timers_fired.set(self.timers_fired.get().saturating_add(n));
+    }
+
+    pub fn incr_spawns(&self) {
+        self.spawns.set(self.spawns.get().saturating_add(1));
+    }
+
+    pub fn snapshot(&self) -> RuntimeMetrics {
+        RuntimeMetrics {
+            busy_us: self.busy.get(),
+            idle_us: self.idle.get(),
+            wakeups: self.wakeups.get(),
+            generations: self.generations.get(),
+            timers_fired: self.timers_fired.get(),
+            spawns: self.spawns.get(),
+        }
+    }
+}
+
+// ===== impl RuntimeMetrics =====
+
+impl RuntimeMetrics {
+    /// Percentage of time spent running tasks, out of the time spent
+    /// either running tasks or idling
+    pub fn cpu_load(&self) -> u8 {
+        let total = self.busy_us.saturating_add(self.idle_us);
+        if total == 0 {
+            return 0;
+        }
+        (self.busy_us * 100 / total) as u8
+    }
+
+    /// CPU load between this snapshot and an earlier one
+    pub fn cpu_load_since(&self, earlier: &RuntimeMetrics) -> u8 {
+        let busy = self.busy_us.saturating_sub(earlier.busy_us);
+        let idle = self.idle_us.saturating_sub(earlier.idle_us);
+        if busy + idle == 0 {
+            return 0;
+        }
+        (busy * 100 / (busy + idle)) as u8
+    }
+}
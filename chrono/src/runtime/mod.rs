@@ -1,12 +1,27 @@
 pub(crate) mod context;
+pub use context::handle;
+
+pub(crate) mod hooks;
+pub use hooks::RuntimeHooks;
+#[cfg(feature = "hooks")]
+pub use hooks::set_hooks;
+
+pub mod idle;
+pub use idle::{IdlePolicy, SleepMode};
+
+mod metrics;
+pub use metrics::RuntimeMetrics;
 
 mod runtime;
-pub use runtime::{Runtime, SpawnError};
+pub use runtime::{Handle, Runtime, Scheduling, SpawnError};
+
+mod task_list;
 
 mod task_queue;
 mod timer_queue;
 
 pub(crate) mod queue {
+    pub(crate) use crate::runtime::task_list::TaskList;
     pub(crate) use crate::runtime::task_queue::{TaskQueue, Generation};
     pub(crate) use crate::runtime::timer_queue::TimerQueue;
 }
\ No newline at end of file
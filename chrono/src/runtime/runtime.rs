@@ -1,20 +1,48 @@
+use core::cell::Cell;
 use core::future::Future;
 use core::marker::PhantomData;
 use core::ptr::NonNull;
 use core::task::{Context, Poll, Waker};
 
+use heapless::Vec;
+
 use super::context;
-use super::queue::{TaskQueue, TimerQueue};
+use super::hooks;
+use super::idle::{self, IdlePolicy, WaitForEvent};
+use super::metrics::{Metrics, RuntimeMetrics};
+use super::queue::{TaskList, TaskQueue, TimerQueue};
 use crate::task::join::JoinHandle;
-use crate::task::{RawTask, Permit};
+use crate::task::{Permit, TaskInfo};
 use crate::task::waker::NoopWaker;
 use crate::time::instant::Instant;
+use crate::time::{self, Duration};
+use crate::watchdog;
 
 pub struct Runtime {
     /// Queue of tasks
     pub(crate) tasks: TaskQueue,
     /// Queue of timers
     pub(crate) timers: TimerQueue,
+    /// List of all live tasks
+    pub(crate) registry: TaskList,
+    /// Polls of a task that take longer than this are reported
+    long_poll: Cell<Option<Duration>>,
+    /// Counters describing the work done by the runtime
+    pub(crate) metrics: Metrics,
+    /// Decides how to sleep when there are no tasks to run
+    idle: Cell<Option<&'static dyn IdlePolicy>>,
+    /// Order in which ready tasks are run
+    scheduling: Cell<Scheduling>,
+}
+
+/// Order in which the runtime runs ready tasks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Scheduling {
+    /// Run tasks in the order they were woken
+    Fifo,
+    /// Run the task with the earliest deadline first. Tasks woken while a
+    /// batch is running still wait for the next batch
+    Edf,
 }
 
 /// Handle to the runtime
@@ -36,8 +64,17 @@ impl Runtime {
     pub const fn new() -> Runtime {
         let tasks = TaskQueue::new();
         let timers = TimerQueue::new();
+        let registry = TaskList::new();
 
-        Runtime { tasks, timers }
+        Runtime {
+            tasks,
+            timers,
+            registry,
+            long_poll: Cell::new(None),
+            metrics: Metrics::new(),
+            idle: Cell::new(None),
+            scheduling: Cell::new(Scheduling::Fifo),
+        }
     }
 
     /// Get the handle to the runtime
@@ -55,6 +92,39 @@ impl Runtime {
         self.handle().spawn(permit)
     }
 
+    /// A snapshot of up to `N` live tasks on the runtime, reporting their id,
+    /// name, state, timer expiry and pool slot. Tasks past the first `N` are
+    /// left out
+    pub fn tasks<const N: usize>(&self) -> Vec<TaskInfo, N> {
+        self.registry.snapshot()
+    }
+
+    /// Set the threshold above which a single poll of a task is reported
+    /// as a long poll. A long poll usually means a task is blocking the executor
+    pub fn set_long_poll_threshold(&self, threshold: Option<Duration>) {
+        self.long_poll.replace(threshold);
+    }
+
+    pub fn long_poll_threshold(&self) -> Option<Duration> {
+        self.long_poll.get()
+    }
+
+    /// A snapshot of the runtime's metrics
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Set the policy deciding how the runtime sleeps when it is idle. By
+    /// default it waits for an event
+    pub fn set_idle_policy(&self, policy: &'static dyn IdlePolicy) {
+        self.idle.replace(Some(policy));
+    }
+
+    /// Set the order in which ready tasks are run. Defaults to FIFO
+    pub fn set_scheduling(&self, scheduling: Scheduling) {
+        self.scheduling.replace(scheduling);
+    }
+
     pub fn block_on<F: Future>(&'static self, future: F) -> F::Output {
         // Enter runtime context
         let _enter = context::enter(self.handle());
@@ -76,34 +146,66 @@ impl Runtime {
             // Process all timers
             let now = Instant::now();
             defmt::trace!("Processing timers");
-            self.timers.process(now);
+            let fired = self.timers.process(now);
+            self.metrics.incr_timers_fired(fired);
 
-            // Start the timer if there is a deadline
-            if let Some(deadline) = self.timers.deadline() {
-                let dur = deadline - Instant::now();
-                context::time_driver().start(dur);
-                defmt::trace!("Started timer. Deadline in {}", dur);
-            }
+            // Feed the watchdog if every registered task has checked in
+            watchdog::service(now);
+
+            // Wake up for whichever comes first, the next timer or the next feed
+            let deadline = match (self.timers.deadline(), watchdog::deadline(now)) {
+                (Some(timer), Some(feed)) => Some(if timer < feed { timer } else { feed }),
+                (timer, feed) => timer.or(feed),
+            };
+            let until_deadline =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
 
-            // If the task queue is empty, wait for an event/interrupt
             if self.tasks.is_empty() {
-                defmt::debug!("Waiting for event");
-                cortex_m::asm::wfe()
+                // The task queue is empty so sleep until an event/interrupt.
+                // Start the timer early enough to make up for the wakeup latency
+                let policy = self.idle.get().unwrap_or(&WaitForEvent);
+                let plan = idle::plan(policy, Instant::now(), until_deadline);
+                if let Some(dur) = plan.timer {
+                    context::time_driver().start(dur);
+                    defmt::trace!("Started timer. Deadline in {}", dur);
+                }
+
+                defmt::debug!("Sleeping: {}", plan.mode);
+                hooks::emit(|hooks| hooks.on_park());
+                let start = Instant::now();
+                policy.before_sleep(plan.mode, plan.wake_at);
+                idle::sleep(plan.mode);
+                // The cycle counter stops in Stop mode. Catch up on the time
+                // spent asleep
+                time::advance(policy.after_sleep(plan.mode));
+                self.metrics.record_idle(start.elapsed());
+                hooks::emit(|hooks| hooks.on_unpark());
+            } else if let Some(dur) = until_deadline {
+                context::time_driver().start(dur);
+                defmt::trace!("Started timer. Deadline in {}", dur);
             }
 
             // Prepare the task queue before walking through it
             let generation = self.tasks.prepare();
+            self.metrics.incr_generations();
             defmt::trace!("Processing {}", generation);
+
+            let start = Instant::now();
             loop {
-                let task = self.tasks.pop_front();
+                let task = match self.scheduling.get() {
+                    Scheduling::Fifo => self.tasks.pop_front(),
+                    Scheduling::Edf => self.tasks.pop_earliest(),
+                };
                 match task {
                     Some(task) => {
                         defmt::trace!("{}, {}: Executing", task.id, task.generation);
+                        let _current = context::enter_task(task.as_ptr());
                         task.run()
                     }
                     None => break,
                 }
             }
+            self.metrics.record_busy(start.elapsed());
         }
     }
 }
@@ -120,13 +222,41 @@ impl Handle {
     ) -> Result<JoinHandle<T>, SpawnError> {
         self.spawner.spawn(permit)
     }
+
+    /// A snapshot of up to `N` live tasks on the runtime
+    pub fn tasks<const N: usize>(&self) -> Vec<TaskInfo, N> {
+        self.spawner.rt.tasks()
+    }
+
+    /// Set the threshold above which a single poll of a task is reported
+    pub fn set_long_poll_threshold(&self, threshold: Option<Duration>) {
+        self.spawner.rt.set_long_poll_threshold(threshold)
+    }
+
+    /// A snapshot of the runtime's metrics
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.spawner.rt.metrics()
+    }
+
+    /// Set the policy deciding how the runtime sleeps when it is idle
+    pub fn set_idle_policy(&self, policy: &'static dyn IdlePolicy) {
+        self.spawner.rt.set_idle_policy(policy)
+    }
+
+    /// Set the order in which ready tasks are run
+    pub fn set_scheduling(&self, scheduling: Scheduling) {
+        self.spawner.rt.set_scheduling(scheduling)
+    }
 }
 
 // ===== impl Spawner =====
 
 #[derive(Debug)]
 pub enum SpawnError {
+    /// Every slot of the task's pool is in use
     QueueFull,
+    /// There is no arena installed or no block in it fits the task
+    NoMemory,
 }
 
 impl Spawner {
@@ -134,10 +264,10 @@ impl Spawner {
         &self,
         permit: Permit<F, T>,
     ) -> Result<JoinHandle<T>, SpawnError> {
-        let (memory, future) = permit.acquire()?;
-        let raw = RawTask::new(memory, future);
+        let raw = permit.into_raw()?;
+        let memory = raw.memory();
 
-        let rt = unsafe { NonNull::new_unchecked(self.rt as *const _ as *mut _) };
+        let mut rt = unsafe { NonNull::new_unchecked(self.rt as *const _ as *mut Runtime) };
         memory.rt.replace(rt);
 
         // pointer to Memory inside of RawTask
@@ -151,6 +281,9 @@ impl Spawner {
         // Get a pointer to our task to store in the queue
         let task = memory.task();
         task.schedule();
+        unsafe { rt.as_mut().registry.push_back(task.as_ptr()) };
+        self.rt.metrics.incr_spawns();
+        hooks::emit(|hooks| hooks.on_spawn(&task.info()));
 
         defmt::debug!("{}, {}: Spawned", task.id, task.generation);
 
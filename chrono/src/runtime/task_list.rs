@@ -0,0 +1,85 @@
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use heapless::Vec;
+
+use crate::task::{Task, TaskInfo};
+
+/// List of all live tasks on the runtime. Tasks are added when spawned
+/// and removed once they complete
+pub(crate) struct TaskList {
+    pub head: Cell<Option<NonNull<Task>>>,
+    pub tail: Cell<Option<NonNull<Task>>>,
+}
+
+// ===== impl TaskList =====
+
+impl TaskList {
+    pub const fn new() -> TaskList {
+        TaskList {
+            head: Cell::new(None),
+            tail: Cell::new(None),
+        }
+    }
+
+    /// Add an element to the back of list
+    pub fn push_back(&mut self, mut task: NonNull<Task>) {
+        unsafe {
+            task.as_mut().registry.set_next(None);
+            task.as_mut().registry.set_prev(self.tail.get());
+
+            match self.tail.get() {
+                Some(mut tail) => tail.as_mut().registry.set_next(Some(task)),
+                None => {
+                    self.head.replace(Some(task));
+                }
+            }
+
+            self.tail.replace(Some(task));
+        }
+    }
+
+    /// Remove an element from anywhere in the list
+    pub fn remove(&mut self, mut task: NonNull<Task>) {
+        unsafe {
+            let task = task.as_mut();
+            let prev = task.registry.prev();
+            let next = task.registry.next();
+
+            match prev {
+                Some(mut prev) => prev.as_mut().registry.set_next(next),
+                None => {
+                    self.head.replace(next);
+                }
+            }
+
+            match next {
+                Some(mut next) => next.as_mut().registry.set_prev(prev),
+                None => {
+                    self.tail.replace(prev);
+                }
+            }
+
+            task.registry.set_next(None);
+            task.registry.set_prev(None);
+        }
+    }
+
+    /// Copy out the info of up to `N` tasks, in the order they were spawned.
+    /// A copy is taken since tasks may be freed once the caller yields
+    pub fn snapshot<const N: usize>(&self) -> Vec<TaskInfo, N> {
+        let mut infos = Vec::new();
+        let mut curr = self.head.get();
+        while let Some(task) = curr {
+            let task = unsafe { task.as_ref() };
+            if infos.push(task.info()).is_err() {
+                break;
+            }
+            curr = task.registry.next();
+        }
+        infos
+    }
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for TaskList {}
@@ -96,6 +96,64 @@ impl TaskQueue {
             }
         }
     }
+
+    /// Pop the task with the earliest deadline off the current batch. Tasks
+    /// without a deadline run after those with one, in the order they were
+    /// pushed
+    pub fn pop_earliest(&self) -> Option<&mut Task> {
+        let mut earliest: Option<NonNull<Task>> = None;
+        let mut curr = self.head.get();
+
+        while let Some(task) = curr {
+            let task_ref = unsafe { task.as_ref() };
+            if task_ref.generation() != self.generation() {
+                break;
+            }
+
+            let is_earlier = match earliest {
+                None => true,
+                Some(e) => match (task_ref.deadline(), unsafe { e.as_ref() }.deadline()) {
+                    (Some(d), Some(e)) => d < e,
+                    (Some(_), None) => true,
+                    _ => false,
+                },
+            };
+            if is_earlier {
+                earliest = Some(task);
+            }
+
+            curr = task_ref.tasks.next();
+        }
+
+        earliest.map(|mut task| {
+            self.remove(task);
+            unsafe { task.as_mut() }
+        })
+    }
+
+    /// Unlink a task from anywhere in the list
+    pub fn remove(&self, mut task: NonNull<Task>) {
+        let task = unsafe { task.as_mut() };
+        let prev = task.tasks.prev();
+        let next = task.tasks.next();
+
+        match prev {
+            Some(mut prev) => unsafe { prev.as_mut().tasks.set_next(next) },
+            None => {
+                self.head.replace(next);
+            }
+        }
+
+        match next {
+            Some(mut next) => unsafe { next.as_mut().tasks.set_prev(prev) },
+            None => {
+                self.tail.replace(prev);
+            }
+        }
+
+        task.tasks.set_next(None);
+        task.tasks.set_prev(None);
+    }
 }
 
 // Safe since we are in a single-threaded environment
@@ -1,6 +1,7 @@
 use core::cell::Cell;
 use core::ptr::NonNull;
 
+use super::hooks;
 use crate::task::Task;
 use crate::time::Instant;
 
@@ -41,15 +42,47 @@ impl TimerQueue {
         }
     }
 
+    /// Remove a timer from anywhere in the list
+    pub fn remove(&mut self, mut task: NonNull<Task>) {
+        unsafe {
+            let task = task.as_mut();
+            let prev = task.timers.prev();
+            let next = task.timers.next();
+
+            match prev {
+                Some(mut prev) => prev.as_mut().timers.set_next(next),
+                None => {
+                    self.head.replace(next);
+                }
+            }
+
+            match next {
+                Some(mut next) => next.as_mut().timers.set_prev(prev),
+                None => {
+                    self.tail.replace(prev);
+                }
+            }
+
+            task.timers.set_next(None);
+            task.timers.set_prev(None);
+            task.clear_expiry();
+        }
+    }
+
     /// Process all timers in the timer queue. If a timer has expired, the
     /// task will be scheduled onto the runtime.
     /// We also take this opportunity to update the deadline, setting it to
-    /// the shortest remaining time of all the timers in the queue
-    pub fn process(&self, now: Instant) {
-        let mut deadline = Instant::max();
+    /// the shortest remaining time of all the timers in the queue.
+    /// Returns the number of timers that fired
+    pub fn process(&self, now: Instant) -> u32 {
+        let mut deadline: Option<Instant> = None;
+        let mut fired = 0;
 
         let mut curr = match self.head.get() {
-            None => return,
+            None => {
+                self.deadline.replace(None);
+                return fired;
+            }
             Some(mut curr) => unsafe { curr.as_mut() },
         };
 
@@ -58,6 +91,8 @@ impl TimerQueue {
                 defmt::debug!("{}, {}: Timer complete", curr.id, curr.generation);
                 // Timer complete so we're going to remove this entry.
                 curr.clear_expiry();
+                fired += 1;
+                hooks::emit(|hooks| hooks.on_timer(&curr.info()));
 
                 // If the prev and next entry is null, we are the only element
                 // in the queue
@@ -66,7 +101,7 @@ impl TimerQueue {
                     self.head.replace(None);
                     self.tail.replace(None);
                     // Schedule the task associated with the timer
-                    curr.schedule();
+                    curr.schedule_if_idle();
                     break;
                 }
 
@@ -83,7 +118,7 @@ impl TimerQueue {
                     // Clear the prev timer
                     curr.timers.set_prev(None);
                     // Schedule the task associated with the timer
-                    curr.schedule();
+                    curr.schedule_if_idle();
                     break;
                 }
 
@@ -100,7 +135,7 @@ impl TimerQueue {
                     // Clear the next timer
                     curr.timers.set_next(None);
                     // Schedule the task associated with the timer
-                    curr.schedule();
+                    curr.schedule_if_idle();
                     // Set curr to the new head for the next loop
                     curr = unsafe { self.head.get().unwrap().as_mut() };
                     continue;
@@ -126,7 +161,7 @@ impl TimerQueue {
                     curr.timers.set_prev(None);
 
                     // Schedule the task associated with the timer
-                    curr.schedule();
+                    curr.schedule_if_idle();
                     // Set curr to the next task in the list for the next loop
                     curr = next.as_mut();
                     continue;
@@ -135,8 +170,8 @@ impl TimerQueue {
 
             // The timer is not finished. Check to see if it should become the new deadline
             if let Some(t) = curr.expiry() {
-                if t < deadline {
-                    deadline = t
+                if deadline.map_or(true, |deadline| t < deadline) {
+                    deadline = Some(t)
                 }
             }
 
@@ -149,10 +184,8 @@ impl TimerQueue {
             curr = unsafe { curr.timers.next().unwrap().as_mut() };
         }
 
-        if deadline != Instant::max() {
-            self.deadline.replace(Some(deadline));
-        } else {
-            self.deadline.replace(None);
-        }
+        self.deadline.replace(deadline);
+
+        fired
     }
 }
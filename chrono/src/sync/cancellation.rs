@@ -0,0 +1,211 @@
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::wait_queue::{WaitQueue, Waiter};
+
+/// Maximum depth of a token below its root. The root itself is at depth 0
+pub const MAX_TOKEN_DEPTH: usize = 4;
+
+/// A token for cooperative cancellation. Tasks wait on
+/// [`cancelled`](CancellationToken::cancelled) and stop cleanly once
+/// [`cancel`](CancellationToken::cancel) is called.
+///
+/// Cancelling a token also cancels its children. Each token holds up to `N`
+/// waiting tasks. Tasks waiting on a child also wait on every ancestor, so
+/// `N` should cover the children's waiters too. A task stops waiting once its
+/// [`Cancelled`] future is dropped
+///
+/// ```ignore
+/// static SHUTDOWN: CancellationToken<'static, 8> = CancellationToken::new();
+///
+/// let token = SHUTDOWN.child_token();
+/// chrono::spawn(handler(token))?;
+/// ```
+pub struct CancellationToken<'a, const N: usize> {
+    parent: Option<&'a CancellationToken<'a, N>>,
+    /// Number of ancestors of the token
+    depth: usize,
+    cancelled: Cell<bool>,
+    waiters: RefCell<WaitQueue<N>>,
+}
+
+/// Future returned by [`CancellationToken::cancelled`]
+pub struct Cancelled<'t, 'a, const N: usize> {
+    token: &'t CancellationToken<'a, N>,
+    /// Place in the waiter list of the token and of each of its ancestors,
+    /// in that order. They leave the lists when the future is dropped
+    waiters: [Waiter; MAX_TOKEN_DEPTH + 1],
+}
+
+// ===== impl CancellationToken =====
+
+impl<'a, const N: usize> CancellationToken<'a, N> {
+    pub const fn new() -> CancellationToken<'a, N> {
+        CancellationToken {
+            parent: None,
+            depth: 0,
+            cancelled: Cell::new(false),
+            waiters: RefCell::new(WaitQueue::new()),
+        }
+    }
+
+    /// A token that is cancelled along with this one. Cancelling the child
+    /// does not cancel this token
+    ///
+    /// # Panics
+    ///
+    /// Panics if the child would be more than [`MAX_TOKEN_DEPTH`] levels below
+    /// the root token
+    pub fn child_token(&'a self) -> CancellationToken<'a, N> {
+        assert!(
+            self.depth < MAX_TOKEN_DEPTH,
+            "cancellation tokens nested too deeply"
+        );
+
+        CancellationToken {
+            parent: Some(self),
+            depth: self.depth + 1,
+            cancelled: Cell::new(false),
+            waiters: RefCell::new(WaitQueue::new()),
+        }
+    }
+
+    /// Cancel the token and its children, waking every task waiting on them
+    pub fn cancel(&self) {
+        if self.cancelled.replace(true) {
+            return;
+        }
+
+        defmt::debug!("Cancelling token");
+        self.waiters.borrow_mut().notify_all();
+    }
+
+    /// Whether the token or one of its ancestors has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get() || self.parent.map_or(false, |p| p.is_cancelled())
+    }
+
+    /// Wait until the token is cancelled
+    pub fn cancelled(&self) -> Cancelled<'_, 'a, N> {
+        Cancelled {
+            token: self,
+            waiters: core::array::from_fn(|_| Waiter::new()),
+        }
+    }
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl<'a, const N: usize> Sync for CancellationToken<'a, N> {}
+
+// ===== impl Cancelled =====
+
+impl<'t, 'a, const N: usize> Cancelled<'t, 'a, N> {
+    /// The token followed by its ancestors, each with the waiter for it
+    fn chain(self: Pin<&Self>) -> impl Iterator<Item = (&CancellationToken<'a, N>, Pin<&Waiter>)> {
+        let this = self.get_ref();
+        let tokens = core::iter::successors(Some(this.token), |token| token.parent);
+        // Safe since the waiters are pinned along with the future
+        let waiters = this.waiters.iter().map(|w| unsafe { Pin::new_unchecked(w) });
+        tokens.zip(waiters)
+    }
+
+    /// Leave the waiter list of every token
+    fn remove(self: Pin<&Self>) {
+        for (token, waiter) in self.chain() {
+            unsafe { token.waiters.borrow_mut().remove(waiter) };
+        }
+    }
+}
+
+impl<'t, 'a, const N: usize> Future for Cancelled<'t, 'a, N> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.into_ref();
+        if this.token.is_cancelled() {
+            this.remove();
+            return Poll::Ready(());
+        }
+
+        // Wait on the ancestors too since they don't know about their
+        // children. A full waiter list wakes the task to check again
+        for (token, waiter) in this.chain() {
+            unsafe { token.waiters.borrow_mut().wait(waiter, cx.waker()) };
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<'t, 'a, const N: usize> Drop for Cancelled<'t, 'a, N> {
+    fn drop(&mut self) {
+        // Safe since the future is not moved again
+        unsafe { Pin::new_unchecked(&*self) }.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::poll;
+    use std::boxed::Box;
+
+    #[test]
+    fn dropped_waiter_leaves_list() {
+        let token: CancellationToken<1> = CancellationToken::new();
+        let woken = [Cell::new(false), Cell::new(false)];
+
+        let mut first = Box::pin(token.cancelled());
+        assert!(poll(&mut first, &woken[0]).is_pending());
+        drop(first);
+
+        // There is room again, so the second waiter isn't woken to poll
+        let mut second = Box::pin(token.cancelled());
+        assert!(poll(&mut second, &woken[1]).is_pending());
+        assert!(!woken[1].get());
+
+        token.cancel();
+        assert!(!woken[0].get());
+        assert!(woken[1].get());
+        assert!(poll(&mut second, &woken[1]).is_ready());
+    }
+
+    #[test]
+    fn child_waiter_leaves_ancestors() {
+        let root: CancellationToken<1> = CancellationToken::new();
+        let child = root.child_token();
+        let woken = [Cell::new(false), Cell::new(false)];
+
+        let mut on_child = Box::pin(child.cancelled());
+        assert!(poll(&mut on_child, &woken[0]).is_pending());
+        drop(on_child);
+
+        let mut on_root = Box::pin(root.cancelled());
+        assert!(poll(&mut on_root, &woken[1]).is_pending());
+        assert!(!woken[1].get());
+        drop(on_root);
+
+        // Cancelling the root wakes a task waiting on the child
+        let mut on_child = Box::pin(child.cancelled());
+        assert!(poll(&mut on_child, &woken[0]).is_pending());
+        root.cancel();
+        assert!(woken[0].get());
+        assert!(poll(&mut on_child, &woken[0]).is_ready());
+        assert!(child.is_cancelled());
+    }
+
+    #[test]
+    #[should_panic]
+    fn child_token_depth_is_bounded() {
+        let root: CancellationToken<1> = CancellationToken::new();
+        let t1 = root.child_token();
+        let t2 = t1.child_token();
+        let t3 = t2.child_token();
+        let t4 = t3.child_token();
+        let _t5 = t4.child_token();
+    }
+}
@@ -0,0 +1,4 @@
+mod cancellation;
+pub use cancellation::{CancellationToken, Cancelled, MAX_TOKEN_DEPTH};
+
+pub(crate) mod wait_queue;
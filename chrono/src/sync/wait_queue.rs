@@ -0,0 +1,300 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomPinned;
+use core::mem;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::Waker;
+
+/// A FIFO of up to `W` waiting tasks. Waking pops the waiter at the front, so
+/// tasks are served in the order they started waiting.
+///
+/// The list is intrusive: its nodes are [`Waiter`]s stored in the waiting
+/// futures, which are pinned while they wait. The queue only holds the ends
+/// of the list. A waiter keeps its place in line until it is woken or removed
+pub(crate) struct WaitQueue<const W: usize> {
+    head: Link,
+    tail: Link,
+    /// Waiters linked in the queue
+    len: usize,
+    /// Waiters woken but not yet removed
+    notified: usize,
+}
+
+/// A task's place in a [`WaitQueue`], kept in the future that waits
+pub(crate) struct Waiter {
+    node: UnsafeCell<Node>,
+    /// Linked waiters are pointed to by their neighbours
+    _pinned: PhantomPinned,
+}
+
+struct Node {
+    state: State,
+    prev: Link,
+    next: Link,
+}
+
+type Link = Option<NonNull<Waiter>>;
+
+enum State {
+    Idle,
+    /// Linked in the queue
+    Waiting(Waker),
+    /// Woken and taken out of the queue, but still holding its turn
+    Notified,
+}
+
+// ===== impl WaitQueue =====
+
+impl<const W: usize> WaitQueue<W> {
+    pub const fn new() -> WaitQueue<W> {
+        WaitQueue {
+            head: None,
+            tail: None,
+            len: 0,
+            notified: 0,
+        }
+    }
+
+    /// Whether any task is waiting in line or has been woken and not yet
+    /// taken its turn
+    pub fn has_waiters(&self) -> bool {
+        self.head.is_some() || self.notified > 0
+    }
+
+    /// Wait in the queue. A waiter that was woken without getting what it
+    /// waited for goes back to the front. If `W` tasks are already waiting
+    /// the task is woken right away so it polls again rather than miss a
+    /// wakeup
+    ///
+    /// # Safety
+    ///
+    /// The waiter must only wait in this queue, and must be removed with
+    /// [`remove`](Self::remove) before it is dropped
+    pub unsafe fn wait(&mut self, waiter: Pin<&Waiter>, waker: &Waker) {
+        let ptr = NonNull::from(waiter.get_ref());
+        let node = Waiter::node(ptr);
+        match &mut node.state {
+            State::Waiting(w) => {
+                if !w.will_wake(waker) {
+                    *w = waker.clone();
+                }
+            }
+            State::Notified => {
+                node.state = State::Waiting(waker.clone());
+                self.notified -= 1;
+                self.link_front(ptr);
+            }
+            State::Idle if self.len + self.notified == W => {
+                defmt::trace!("Wait queue full");
+                waker.wake_by_ref();
+            }
+            State::Idle => {
+                node.state = State::Waiting(waker.clone());
+                self.link_back(ptr);
+            }
+        }
+    }
+
+    /// Give up the waiter's place in line. Returns whether it had been woken,
+    /// in which case the wakeup should be passed on
+    ///
+    /// # Safety
+    ///
+    /// The waiter must be idle or waiting in this queue
+    pub unsafe fn remove(&mut self, waiter: Pin<&Waiter>) -> bool {
+        let ptr = NonNull::from(waiter.get_ref());
+        match mem::replace(&mut Waiter::node(ptr).state, State::Idle) {
+            State::Idle => false,
+            State::Waiting(_) => {
+                self.unlink(ptr);
+                false
+            }
+            State::Notified => {
+                self.notified -= 1;
+                true
+            }
+        }
+    }
+
+    /// Wake the waiter at the front of the queue
+    pub fn notify_one(&mut self) {
+        if let Some(head) = self.head {
+            // Safe since linked waiters are removed before they are dropped
+            let state = unsafe {
+                self.unlink(head);
+                mem::replace(&mut Waiter::node(head).state, State::Notified)
+            };
+            self.notified += 1;
+            if let State::Waiting(waker) = state {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Wake every waiter in the queue
+    pub fn notify_all(&mut self) {
+        while self.head.is_some() {
+            self.notify_one();
+        }
+    }
+
+    unsafe fn link_back(&mut self, ptr: NonNull<Waiter>) {
+        let node = Waiter::node(ptr);
+        node.prev = self.tail;
+        node.next = None;
+        match self.tail {
+            Some(tail) => Waiter::node(tail).next = Some(ptr),
+            None => self.head = Some(ptr),
+        }
+        self.tail = Some(ptr);
+        self.len += 1;
+    }
+
+    unsafe fn link_front(&mut self, ptr: NonNull<Waiter>) {
+        let node = Waiter::node(ptr);
+        node.prev = None;
+        node.next = self.head;
+        match self.head {
+            Some(head) => Waiter::node(head).prev = Some(ptr),
+            None => self.tail = Some(ptr),
+        }
+        self.head = Some(ptr);
+        self.len += 1;
+    }
+
+    unsafe fn unlink(&mut self, ptr: NonNull<Waiter>) {
+        let node = Waiter::node(ptr);
+        let (prev, next) = (node.prev.take(), node.next.take());
+        match prev {
+            Some(prev) => Waiter::node(prev).next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => Waiter::node(next).prev = prev,
+            None => self.tail = prev,
+        }
+        self.len -= 1;
+    }
+}
+
+// ===== impl Waiter =====
+
+impl Waiter {
+    pub const fn new() -> Waiter {
+        Waiter {
+            node: UnsafeCell::new(Node {
+                state: State::Idle,
+                prev: None,
+                next: None,
+            }),
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Whether the waiter is neither in line nor holding its turn
+    pub fn is_idle(&self) -> bool {
+        matches!(unsafe { &(*self.node.get()).state }, State::Idle)
+    }
+
+    /// Whether the waiter has been woken and it is its turn
+    pub fn is_notified(&self) -> bool {
+        matches!(unsafe { &(*self.node.get()).state }, State::Notified)
+    }
+
+    /// The node of a waiter. The reference must not outlive the waiter or
+    /// overlap with another one to the same node
+    unsafe fn node<'a>(ptr: NonNull<Waiter>) -> &'a mut Node {
+        &mut *ptr.as_ref().node.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::waker;
+    use core::cell::Cell;
+
+    // The waiters in the tests stay where they are
+    fn pinned(waiter: &Waiter) -> Pin<&Waiter> {
+        unsafe { Pin::new_unchecked(waiter) }
+    }
+
+    #[test]
+    fn wakes_in_order() {
+        let mut queue: WaitQueue<4> = WaitQueue::new();
+        let woken = [Cell::new(false), Cell::new(false), Cell::new(false)];
+        let waiters = [Waiter::new(), Waiter::new(), Waiter::new()];
+        for (flag, waiter) in woken.iter().zip(waiters.iter()) {
+            unsafe { queue.wait(pinned(waiter), &waker(flag)) };
+        }
+
+        queue.notify_one();
+        assert!(woken[0].replace(false));
+        assert!(!woken[1].get());
+        queue.notify_one();
+        assert!(woken[1].replace(false));
+
+        // A woken waiter that waits again goes back to the front
+        unsafe { queue.wait(pinned(&waiters[0]), &waker(&woken[0])) };
+        queue.notify_one();
+        assert!(woken[0].get());
+        assert!(!woken[2].get());
+    }
+
+    #[test]
+    fn remove_passes_on_wakeup() {
+        let mut queue: WaitQueue<2> = WaitQueue::new();
+        let woken = [Cell::new(false), Cell::new(false)];
+        let a = Waiter::new();
+        let b = Waiter::new();
+        unsafe {
+            queue.wait(pinned(&a), &waker(&woken[0]));
+            queue.wait(pinned(&b), &waker(&woken[1]));
+            assert!(!queue.remove(pinned(&b)));
+        }
+
+        queue.notify_one();
+        assert!(woken[0].get());
+        assert!(unsafe { queue.remove(pinned(&a)) });
+
+        // Nothing left to wake
+        queue.notify_one();
+        assert!(!woken[1].get());
+    }
+
+    #[test]
+    fn tracks_notified_waiters() {
+        let mut queue: WaitQueue<2> = WaitQueue::new();
+        let woken = Cell::new(false);
+        let waiter = Waiter::new();
+        assert!(!queue.has_waiters());
+
+        unsafe { queue.wait(pinned(&waiter), &waker(&woken)) };
+        assert!(queue.has_waiters());
+        assert!(!waiter.is_notified());
+
+        // Woken but still holding its turn
+        queue.notify_one();
+        assert!(queue.has_waiters());
+        assert!(waiter.is_notified());
+
+        unsafe { queue.remove(pinned(&waiter)) };
+        assert!(!queue.has_waiters());
+        assert!(waiter.is_idle());
+    }
+
+    #[test]
+    fn full_queue_wakes_immediately() {
+        let mut queue: WaitQueue<1> = WaitQueue::new();
+        let woken = [Cell::new(false), Cell::new(false)];
+        let a = Waiter::new();
+        let b = Waiter::new();
+        unsafe {
+            queue.wait(pinned(&a), &waker(&woken[0]));
+            queue.wait(pinned(&b), &waker(&woken[1]));
+        }
+        assert!(!woken[0].get());
+        assert!(woken[1].get());
+        assert!(b.is_idle());
+    }
+}
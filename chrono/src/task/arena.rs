@@ -0,0 +1,111 @@
+use core::alloc::Layout;
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// Maximum alignment of the blocks handed out by [`Arena`]
+const BLOCK_ALIGN: usize = 8;
+
+static ARENA: Global = Global(Cell::new(None));
+
+/// Memory shared by tasks spawned through
+/// [`Permit::from_arena`](crate::task::Permit::from_arena)
+pub trait TaskArena {
+    /// Allocate a block fitting `layout`. Returns the block and its index,
+    /// which is reported as the task's slot
+    fn alloc(&self, layout: Layout) -> Option<(NonNull<u8>, usize)>;
+
+    /// Return a block allocated by [`alloc`](TaskArena::alloc)
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc` on this arena and not freed since
+    unsafe fn free(&self, ptr: NonNull<u8>);
+}
+
+/// An arena of `N` blocks of `BLOCK` bytes each. A task fits in a block if
+/// its `Memory` is at most `BLOCK` bytes and aligned to at most 8 bytes
+///
+/// ```ignore
+/// static ARENA: Arena<512, 8> = Arena::new();
+/// chrono::task::set_arena(&ARENA);
+/// ```
+pub struct Arena<const BLOCK: usize, const N: usize> {
+    blocks: UnsafeCell<[Block<BLOCK>; N]>,
+    used: UnsafeCell<[bool; N]>,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(8))]
+struct Block<const S: usize>([MaybeUninit<u8>; S]);
+
+struct Global(Cell<Option<&'static dyn TaskArena>>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Global {}
+
+// ===== impl Arena =====
+
+impl<const BLOCK: usize, const N: usize> Arena<BLOCK, N> {
+    pub const fn new() -> Arena<BLOCK, N> {
+        Arena {
+            blocks: UnsafeCell::new([Block([MaybeUninit::uninit(); BLOCK]); N]),
+            used: UnsafeCell::new([false; N]),
+        }
+    }
+
+    /// Number of blocks in use
+    pub fn used(&self) -> usize {
+        let used = unsafe { &*self.used.get() };
+        used.iter().filter(|used| **used).count()
+    }
+}
+
+impl<const BLOCK: usize, const N: usize> TaskArena for Arena<BLOCK, N> {
+    fn alloc(&self, layout: Layout) -> Option<(NonNull<u8>, usize)> {
+        if layout.size() > BLOCK || layout.align() > BLOCK_ALIGN {
+            defmt::warn!(
+                "Task of {} bytes aligned to {} does not fit in a block of {} bytes",
+                layout.size(),
+                layout.align(),
+                BLOCK
+            );
+            return None;
+        }
+
+        let used = unsafe { &mut *self.used.get() };
+        let index = used.iter().position(|used| !*used)?;
+        used[index] = true;
+
+        let blocks = self.blocks.get() as *mut Block<BLOCK>;
+        let ptr = unsafe { blocks.add(index) as *mut u8 };
+        Some((unsafe { NonNull::new_unchecked(ptr) }, index))
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>) {
+        let blocks = self.blocks.get() as *mut Block<BLOCK>;
+        let index = (ptr.as_ptr() as *mut Block<BLOCK>).offset_from(blocks) as usize;
+
+        let used = &mut *self.used.get();
+        used[index] = false;
+    }
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl<const BLOCK: usize, const N: usize> Sync for Arena<BLOCK, N> {}
+
+// ===== Functions =====
+
+/// Install the arena that tasks spawned through
+/// [`Permit::from_arena`](crate::task::Permit::from_arena) draw from
+pub fn set_arena(arena: &'static dyn TaskArena) {
+    ARENA.0.replace(Some(arena));
+}
+
+/// Allocate a block from the installed arena. Returns the arena along with
+/// the block so it can be freed there
+pub(crate) fn alloc(layout: Layout) -> Option<(NonNull<u8>, usize, &'static dyn TaskArena)> {
+    let arena = ARENA.0.get()?;
+    let (ptr, slot) = arena.alloc(layout)?;
+    Some((ptr, slot, arena))
+}
@@ -0,0 +1,51 @@
+use core::cell::Cell;
+use core::task::{Context, Poll};
+
+/// Number of consecutive ready results a task gets from chrono's futures
+/// before it is forced to yield
+pub const BUDGET: u8 = 32;
+
+static CURRENT: Budget = Budget(Cell::new(None));
+
+/// Budget of the task currently being polled. `None` outside of a task,
+/// in which case it is unconstrained
+struct Budget(Cell<Option<u8>>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Budget {}
+
+/// Clears the budget when dropped
+pub(crate) struct BudgetGuard;
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        CURRENT.0.replace(None);
+    }
+}
+
+/// Give the task about to be polled a fresh budget
+pub(crate) fn budget() -> BudgetGuard {
+    CURRENT.0.replace(Some(BUDGET));
+    BudgetGuard
+}
+
+/// Poll `f` if the current task has budget left. A ready result uses up one
+/// unit of the budget. Once it is used up, the task is woken and `Pending` is
+/// returned so other tasks get a chance to run
+pub(crate) fn poll_budgeted<T>(
+    cx: &mut Context<'_>,
+    f: impl FnOnce(&mut Context<'_>) -> Poll<T>,
+) -> Poll<T> {
+    let budget = CURRENT.0.get();
+    if budget == Some(0) {
+        defmt::trace!("Budget used up. Yielding");
+        cx.waker().wake_by_ref();
+        return Poll::Pending;
+    }
+
+    let res = f(cx);
+    if res.is_ready() {
+        CURRENT.0.replace(budget.map(|b| b - 1));
+    }
+    res
+}
@@ -0,0 +1,38 @@
+use super::task::TaskId;
+use crate::runtime::context;
+
+/// The task currently being run by the executor
+#[derive(Clone, Copy)]
+pub struct Current {
+    /// Unique identifier of the task
+    pub id: TaskId,
+    /// Name given to the task, if any
+    pub name: Option<&'static str>,
+    /// Generation of the task queue the task is running in
+    pub generation: u8,
+}
+
+impl defmt::Format for Current {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}:{}",
+            self.id,
+            self.name.unwrap_or("<unnamed>")
+        )
+    }
+}
+
+/// The task currently being run, or `None` when called from outside of a
+/// task, such as from the future passed to `block_on`
+pub fn current() -> Option<Current> {
+    context::current_task().map(|task| {
+        let task = unsafe { task.as_ref() };
+        let info = task.info();
+        Current {
+            id: info.id,
+            name: info.name,
+            generation: task.generation().0,
+        }
+    })
+}
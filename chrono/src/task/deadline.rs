@@ -0,0 +1,14 @@
+use crate::runtime::context;
+use crate::time::Instant;
+
+/// Set the deadline of the current task for the next time it is woken. It
+/// takes precedence over the deadline given through
+/// [`Permit::deadline`](crate::task::Permit::deadline)
+///
+/// # Panics
+///
+/// Panics if called outside of a task
+pub fn set_deadline(deadline: Instant) {
+    let task = context::current_task().expect("`set_deadline` called outside of a task");
+    unsafe { task.as_ref().set_next_deadline(deadline) };
+}
@@ -0,0 +1,28 @@
+use core::future::Future;
+use core::mem;
+
+use super::raw::Memory;
+
+/// RAM taken by the task pool of a `#[chrono::alloc]` function. Emitted by
+/// the macro as `<FUNCTION>_FOOTPRINT`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Footprint {
+    /// Size of a single task in bytes
+    pub size: usize,
+    /// Number of tasks in the pool. Zero for tasks drawing from the arena
+    pub count: usize,
+}
+
+impl Footprint {
+    pub const fn new<F: Future<Output = T>, T>(count: usize) -> Footprint {
+        Footprint {
+            size: mem::size_of::<Memory<F, T>>(),
+            count,
+        }
+    }
+
+    /// Total size of the pool in bytes
+    pub const fn total(&self) -> usize {
+        self.size * self.count
+    }
+}
@@ -1,9 +1,27 @@
+use core::ptr::NonNull;
 use core::task::Waker;
 
+use crate::task::arena::TaskArena;
+use crate::task::local::Entry;
 use crate::task::raw::TaskVTable;
 use crate::task::state::State;
+use crate::task::stats::Stats;
 use crate::task::Task;
 use crate::time::instant::Instant;
+use crate::time::Duration;
+
+/// Where the memory of a task comes from
+#[derive(Clone, Copy)]
+pub enum Storage {
+    /// A static pool, declared by `#[chrono::alloc]` or a `TaskPool`
+    Pool,
+    /// The task arena the memory was allocated from. Kept so the memory is
+    /// returned to it even if another arena has been installed since
+    Arena(&'static dyn TaskArena),
+    /// The heap, through `spawn_boxed`
+    #[cfg(feature = "alloc")]
+    Heap,
+}
 
 pub struct Header {
     pub task: Task,
@@ -11,6 +29,24 @@ pub struct Header {
     pub expiry: Option<Instant>,
     pub waker: Option<Waker>,
     pub vtable: &'static TaskVTable, // Why &'static? Think cause they are fns
+    /// Name given to the task through `#[chrono::alloc(name = "...")]`
+    pub name: Option<&'static str>,
+    /// Index of the task's memory within the pool it was allocated from
+    pub slot: usize,
+    /// Poll statistics of the task
+    pub stats: Stats,
+    /// Deadline given to the task relative to each time it is scheduled
+    pub relative_deadline: Option<Duration>,
+    /// The instant the task should have run by. Used to order tasks when
+    /// scheduling earliest deadline first
+    pub deadline: Option<Instant>,
+    /// Deadline for the next time the task is scheduled, set through
+    /// [`set_deadline`](crate::task::set_deadline)
+    pub next_deadline: Option<Instant>,
+    /// Task-local values set while the task is polled
+    pub locals: Option<NonNull<Entry>>,
+    /// Where the memory of the task comes from
+    pub storage: Storage,
 }
 
 impl Header {
@@ -0,0 +1,39 @@
+use super::state::State;
+use super::stats::Stats;
+use super::task::TaskId;
+use crate::time::Instant;
+
+/// A snapshot of a live task, as reported by [`Runtime::tasks`](crate::Runtime::tasks)
+#[derive(Clone, Copy)]
+pub struct TaskInfo {
+    /// Unique identifier of the task
+    pub id: TaskId,
+    /// Name given to the task, if any
+    pub name: Option<&'static str>,
+    /// State flags of the task
+    pub state: State,
+    /// If the task is sleeping, the instant its timer expires
+    pub expiry: Option<Instant>,
+    /// Index of the task's memory within its pool
+    pub slot: usize,
+    /// Poll statistics of the task
+    pub stats: Stats,
+    /// If the task is scheduled with a deadline, the instant it should run by
+    pub deadline: Option<Instant>,
+}
+
+impl defmt::Format for TaskInfo {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Task {{ id={}, name={}, slot={}, expiry={}, deadline={}, {}, {} }}",
+            self.id,
+            self.name.unwrap_or("<unnamed>"),
+            self.slot,
+            self.expiry,
+            self.deadline,
+            self.state,
+            self.stats
+        )
+    }
+}
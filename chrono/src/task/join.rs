@@ -1,3 +1,4 @@
+use core::fmt;
 use core::future::Future;
 use core::marker::PhantomData;
 use core::pin::Pin;
@@ -13,12 +14,83 @@ pub struct JoinHandle<T> {
     pub(crate) _marker: PhantomData<T>,
 }
 
+/// Why the output of a task could not be retrieved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was cancelled before it completed
+    Cancelled,
+    /// The output has already been taken
+    Consumed,
+}
+
+/// Error returned by [`JoinHandle::try_join`]
+#[derive(Debug)]
+pub enum TryJoinError<T> {
+    /// The task has not completed yet. The handle is given back
+    Pending(JoinHandle<T>),
+    /// The task completed without an output
+    Join(JoinError),
+}
+
+// ===== impl JoinHandle =====
+
+impl<T> JoinHandle<T> {
+    /// Whether the task has completed
+    pub fn is_finished(&self) -> bool {
+        let header = self.raw.as_ptr() as *const Header;
+        unsafe { (*header).state.is_complete() }
+    }
+
+    /// Get the output of the task without waiting for it
+    pub fn try_join(self) -> Result<T, TryJoinError<T>> {
+        if !self.is_finished() {
+            return Err(TryJoinError::Pending(self));
+        }
+        self.take_output().map_err(TryJoinError::Join)
+    }
+
+    /// Let the task run to completion on its own. Its output is dropped
+    /// once it completes and its slot is freed for another task
+    pub fn detach(self) {
+        // The task is released once both it has completed and its handle
+        // is gone, which is all dropping the handle does
+        drop(self)
+    }
+
+    /// Cancel the task, dropping its future
+    pub(crate) fn abort(&self) {
+        let raw = self.raw.as_ptr();
+        let header = raw as *const Header;
+        unsafe { ((*header).vtable.abort)(raw) }
+    }
+
+    /// Move the output out of a complete task
+    fn take_output(&self) -> Result<T, JoinError> {
+        let raw = self.raw.as_ptr();
+        let mut output = Poll::Pending;
+
+        unsafe {
+            let header = &*(raw as *const Header);
+            (header.vtable.get_output)(raw, &mut output as *mut _ as *mut ())?;
+        }
+
+        match output {
+            Poll::Ready(output) => Ok(output),
+            Poll::Pending => Err(JoinError::Consumed),
+        }
+    }
+}
+
+// The output is never pinned, only moved out once the task completes
+impl<T> Unpin for JoinHandle<T> {}
+
+/// Resolves to the output of the task, or an error if the task was cancelled
+/// or the handle is polled again after returning the output
 impl<T> Future for JoinHandle<T> {
-    type Output = T;
+    type Output = Result<T, JoinError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let raw = self.raw.as_ptr();
-        let mut output = Poll::Pending;
 
         unsafe {
             let header = &mut *(raw as *mut Header);
@@ -34,13 +106,13 @@ impl<T> Future for JoinHandle<T> {
                 // Register waker with the task
                 header.register_waker(cx.waker());
                 header.state.set_join_waker();
-            } else {
-                defmt::trace!("{}: JoinHandle ready", id);
-                (header.vtable.get_output)(self.raw.as_ptr(), &mut output as *mut _ as *mut ());
+                return Poll::Pending;
             }
+
+            defmt::trace!("{}: JoinHandle ready", id);
         }
 
-        output
+        Poll::Ready(self.take_output())
     }
 }
 
@@ -55,3 +127,33 @@ impl<T> Drop for JoinHandle<T> {
         }
     }
 }
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = self.raw.as_ptr() as *const Header;
+        let id = unsafe { (*header).task.id };
+        f.debug_struct("JoinHandle")
+            .field("id", &id.as_u64())
+            .finish()
+    }
+}
+
+// ===== impl JoinError =====
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => write!(f, "task was cancelled"),
+            JoinError::Consumed => write!(f, "task output was already taken"),
+        }
+    }
+}
+
+impl defmt::Format for JoinError {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            JoinError::Cancelled => defmt::write!(f, "task was cancelled"),
+            JoinError::Consumed => defmt::write!(f, "task output was already taken"),
+        }
+    }
+}
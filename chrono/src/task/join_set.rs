@@ -0,0 +1,115 @@
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use heapless::Vec;
+
+use super::join::{JoinError, JoinHandle};
+use super::raw::Permit;
+use crate::runtime::SpawnError;
+
+/// A group of up to `N` tasks. Their outputs are collected as the tasks
+/// complete. Tasks still running when the set is dropped are cancelled
+///
+/// ```ignore
+/// let mut set: JoinSet<(), 32> = JoinSet::new();
+/// for _ in 0..32 {
+///     set.spawn(handle_tcp_conn())?;
+/// }
+/// while let Some(res) = set.join_next().await {
+///     res?;
+/// }
+/// ```
+pub struct JoinSet<T, const N: usize> {
+    handles: Vec<JoinHandle<T>, N>,
+}
+
+/// Error returned by [`JoinSet::spawn`]
+#[derive(Debug)]
+pub enum JoinSetError {
+    /// The set already holds `N` tasks
+    Full,
+    /// The task could not be spawned
+    Spawn(SpawnError),
+}
+
+// ===== impl JoinSet =====
+
+impl<T, const N: usize> JoinSet<T, N> {
+    pub const fn new() -> JoinSet<T, N> {
+        JoinSet {
+            handles: Vec::new(),
+        }
+    }
+
+    /// Spawn a task onto the current runtime and add it to the set. Fails
+    /// with [`JoinSetError::Full`] if the set is full
+    pub fn spawn<F>(&mut self, permit: Permit<F, T>) -> Result<(), JoinSetError>
+    where
+        F: Future<Output = T>,
+    {
+        if self.handles.is_full() {
+            return Err(JoinSetError::Full);
+        }
+
+        let handle = crate::task::spawn(permit).map_err(JoinSetError::Spawn)?;
+        // Cannot fail since we checked there is room
+        let _ = self.handles.push(handle);
+        Ok(())
+    }
+
+    /// Add an already spawned task to the set. The handle is given back if
+    /// the set is full
+    pub fn push(&mut self, handle: JoinHandle<T>) -> Result<(), JoinHandle<T>> {
+        self.handles.push(handle)
+    }
+
+    /// Number of tasks in the set
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Wait for a task to complete and return its output, or why there is
+    /// none. Returns `None` once the set is empty.
+    ///
+    /// Tasks are checked by their position in the set, which changes as
+    /// tasks are removed. If several completed since the last call, the
+    /// order they are returned in is unspecified and need not be the order
+    /// they completed in
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    pub fn poll_join_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, JoinError>>> {
+        if self.handles.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        for i in 0..self.handles.len() {
+            if let Poll::Ready(output) = Pin::new(&mut self.handles[i]).poll(cx) {
+                self.handles.swap_remove(i);
+                return Poll::Ready(Some(output));
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Cancel every task in the set and remove them from it
+    pub fn abort_all(&mut self) {
+        for handle in self.handles.iter() {
+            handle.abort();
+        }
+        self.handles.clear();
+    }
+}
+
+impl<T, const N: usize> Drop for JoinSet<T, N> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
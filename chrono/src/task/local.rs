@@ -0,0 +1,243 @@
+use core::cell::Cell;
+use core::fmt;
+use core::future::Future;
+use core::marker::{PhantomData, PhantomPinned};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{Context, Poll};
+
+use crate::runtime::context;
+
+/// Declare task-local keys. A value is given to a key for the duration of a
+/// future with [`LocalKey::scope`] and read inside it with [`LocalKey::with`]
+///
+/// ```ignore
+/// chrono::task_local! {
+///     static REQUEST_ID: u32;
+/// }
+///
+/// REQUEST_ID.scope(7, async {
+///     REQUEST_ID.with(|id| defmt::info!("Handling request {}", id));
+/// }).await;
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+    };
+}
+
+/// A key to a task-local value. Declared with [`task_local!`](crate::task_local)
+pub struct LocalKey<T: 'static> {
+    // Keeps the key from being zero sized so every key has a unique address
+    _id: u8,
+    _marker: PhantomData<T>,
+}
+
+/// A future that gives a task-local key a value while it is polled
+pub struct TaskLocalFuture<T: 'static, F> {
+    key: &'static LocalKey<T>,
+    value: T,
+    entry: Entry,
+    future: F,
+    _pin: PhantomPinned,
+}
+
+/// A task-local value set on the current task. Entries live inside a pinned
+/// [`TaskLocalFuture`] and are pushed onto the task's header while it is polled
+pub struct Entry {
+    key: *const (),
+    value: *const (),
+    next: Option<NonNull<Entry>>,
+}
+
+/// Error returned when a task-local key has no value
+#[derive(Debug)]
+pub struct AccessError;
+
+/// Locals of the `block_on` future, which does not run in a task
+struct Root(Cell<Option<NonNull<Entry>>>);
+
+static ROOT: Root = Root(Cell::new(None));
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Root {}
+
+// ===== impl LocalKey =====
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new() -> LocalKey<T> {
+        LocalKey {
+            _id: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Give the key `value` while `future` is polled
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            key: self,
+            value,
+            entry: Entry {
+                key: ptr::null(),
+                value: ptr::null(),
+                next: None,
+            },
+            future,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Access the value of the key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key has no value in the current task
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        self.try_with(f)
+            .expect("task-local value accessed outside of its scope")
+    }
+
+    /// Access the value of the key, returning an error if it has no value in
+    /// the current task
+    pub fn try_with<R>(&'static self, f: impl FnOnce(&T) -> R) -> Result<R, AccessError> {
+        let key = self as *const _ as *const ();
+        let mut curr = head();
+
+        while let Some(entry) = curr {
+            let entry = unsafe { entry.as_ref() };
+            if entry.key == key {
+                let value = unsafe { &*(entry.value as *const T) };
+                return Ok(f(value));
+            }
+            curr = entry.next;
+        }
+
+        Err(AccessError)
+    }
+
+    /// Get a copy of the value of the key
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key has no value in the current task
+    pub fn get(&'static self) -> T
+    where
+        T: Copy,
+    {
+        self.with(|v| *v)
+    }
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl<T: 'static> Sync for LocalKey<T> {}
+
+// ===== impl TaskLocalFuture =====
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe since nothing is moved out of the pinned future
+        let this = unsafe { self.get_unchecked_mut() };
+
+        this.entry.key = this.key as *const _ as *const ();
+        this.entry.value = &this.value as *const T as *const ();
+        this.entry.next = head();
+        let entry = NonNull::from(&this.entry);
+
+        // The entry is only reachable while the inner future is polled
+        set_head(Some(entry));
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let res = future.poll(cx);
+        set_head(this.entry.next);
+
+        res
+    }
+}
+
+// ===== impl AccessError =====
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task-local value not set")
+    }
+}
+
+// ===== Functions =====
+
+/// First entry of the locals of the current task
+fn head() -> Option<NonNull<Entry>> {
+    match context::current_task() {
+        Some(task) => unsafe { task.as_ref().locals() },
+        None => ROOT.0.get(),
+    }
+}
+
+fn set_head(entry: Option<NonNull<Entry>>) {
+    match context::current_task() {
+        Some(task) => unsafe { task.as_ref().set_locals(entry) },
+        None => {
+            ROOT.0.replace(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::task::waker::NoopWaker;
+    use core::task::Waker;
+    use std::sync::Mutex;
+
+    // Outside of a task every test shares the same locals
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    crate::task_local! {
+        static OUTER: u32;
+        static INNER: u32;
+    }
+
+    fn poll<F: Future>(future: F) -> Poll<F::Output> {
+        let waker = unsafe { Waker::from_raw(NoopWaker::raw()) };
+        let cx = &mut Context::from_waker(&waker);
+        crate::pin!(future);
+        future.as_mut().poll(cx)
+    }
+
+    #[test]
+    fn nested_scopes() {
+        let _lock = LOCK.lock().unwrap();
+
+        let res = poll(OUTER.scope(1, async {
+            let outer = OUTER.get();
+            let (inner, shadowed) = INNER
+                .scope(2, OUTER.scope(3, async { (INNER.get(), OUTER.get()) }))
+                .await;
+            (outer, inner, shadowed, OUTER.get(), INNER.try_with(|_| ()).is_err())
+        }));
+        assert_eq!(res, Poll::Ready((1, 2, 3, 1, true)));
+    }
+
+    #[test]
+    fn access_outside_scope() {
+        let _lock = LOCK.lock().unwrap();
+
+        assert!(OUTER.try_with(|_| ()).is_err());
+        let _ = poll(OUTER.scope(1, async {}));
+        // The entry is gone once the scope is no longer polled
+        assert!(OUTER.try_with(|_| ()).is_err());
+    }
+}
@@ -1,19 +1,59 @@
+mod arena;
+pub use arena::{set_arena, Arena, TaskArena};
+
 mod cell;
 
+pub(crate) mod coop;
+
+mod current;
+pub use current::{current, Current};
+
 pub(crate) mod header;
 
+mod deadline;
+pub use deadline::set_deadline;
+
+mod footprint;
+pub use footprint::Footprint;
+
+mod info;
+pub use info::TaskInfo;
+
 pub(crate) mod join;
-pub use join::JoinHandle;
+pub use join::{JoinError, JoinHandle, TryJoinError};
+
+mod join_set;
+pub use join_set::{JoinSet, JoinSetError};
+
+mod local;
+pub use local::{AccessError, LocalKey, TaskLocalFuture};
+
+mod pool;
+pub use pool::TaskPool;
 
 mod raw;
 pub use raw::{Memory, RawTask, Permit};
 
 mod spawn;
 pub use spawn::spawn;
+#[cfg(feature = "alloc")]
+pub use spawn::spawn_boxed;
+
+mod spawn_macro;
 
 mod state;
+pub use state::State;
+
+mod stats;
+pub use stats::Stats;
+
+mod supervisor;
+pub use supervisor::{Outcome, RestartPolicy, Supervisor, SupervisorError};
 
 mod task;
-pub use task::Task;
+pub use task::{Task, TaskId};
 
 pub(crate) mod waker;
+
+mod yield_now;
+pub use yield_now::yield_now;
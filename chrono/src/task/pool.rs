@@ -0,0 +1,51 @@
+use core::future::Future;
+
+use super::join::JoinHandle;
+use super::raw::{Memory, Permit};
+use crate::runtime::SpawnError;
+
+/// Static storage for up to `N` instances of a task. An alternative to
+/// `#[chrono::alloc]` that can be embedded in other types
+///
+/// ```ignore
+/// type Blink = impl Future<Output = ()>;
+/// static POOL: TaskPool<Blink, 2> = TaskPool::new();
+///
+/// fn start(led: Led) -> Result<JoinHandle<()>, SpawnError> {
+///     POOL.spawn(move || blink(led))
+/// }
+/// ```
+pub struct TaskPool<F, const N: usize>
+where
+    F: Future + 'static,
+{
+    memory: [Memory<F, F::Output>; N],
+}
+
+// ===== impl TaskPool =====
+
+impl<F, const N: usize> TaskPool<F, N>
+where
+    F: Future + 'static,
+{
+    const ALLOC: Memory<F, F::Output> = Memory::alloc();
+
+    pub const fn new() -> TaskPool<F, N> {
+        TaskPool {
+            memory: [Self::ALLOC; N],
+        }
+    }
+
+    /// A permit to spawn `future` into a free slot of the pool
+    pub fn permit(&'static self, future: impl FnOnce() -> F) -> Permit<F, F::Output> {
+        Permit::new(&self.memory, future)
+    }
+
+    /// Spawn `future` into a free slot of the pool onto the current runtime
+    pub fn spawn(
+        &'static self,
+        future: impl FnOnce() -> F,
+    ) -> Result<JoinHandle<F::Output>, SpawnError> {
+        crate::task::spawn(self.permit(future))
+    }
+}
@@ -1,17 +1,25 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::alloc::Layout;
 use core::cell::Cell;
 use core::future::Future;
 use core::marker::PhantomData;
-use core::mem;
+use core::mem::{self, ManuallyDrop};
 use core::pin::Pin;
 use core::ptr::NonNull;
 use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 
+use super::arena;
 use super::cell::UninitCell;
-use super::header::Header;
+use super::coop;
+use super::header::{Header, Storage};
+use super::join::JoinError;
 use super::state::State;
+use super::stats::Stats;
 use super::task::Task;
+use crate::runtime::hooks;
 use crate::runtime::SpawnError;
-use crate::time::Instant;
+use crate::time::{Duration, Instant};
 use crate::Runtime;
 
 // The C representation means we have guarantees on
@@ -46,8 +54,22 @@ where
     F: Future<Output = T> + 'static,
     T: 'static,
 {
-    memory: &'static [Memory<F, T>],
+    source: Source<F, T>,
     future: F,
+    name: Option<&'static str>,
+    deadline: Option<Duration>,
+}
+
+/// Where a permit acquires memory from
+enum Source<F, T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    Pool(&'static [Memory<F, T>]),
+    Arena,
+    #[cfg(feature = "alloc")]
+    Heap,
 }
 
 pub enum Status<F, T>
@@ -64,8 +86,9 @@ pub struct TaskVTable {
     pub(crate) poll: unsafe fn(*const ()),
     pub(crate) schedule: unsafe fn(*const ()),
     pub(crate) schedule_timer: unsafe fn(*const (), Instant),
-    pub(crate) get_output: unsafe fn(*const (), *mut ()),
+    pub(crate) get_output: unsafe fn(*const (), *mut ()) -> Result<(), JoinError>,
     pub(crate) drop_join_handle: unsafe fn(*const ()),
+    pub(crate) abort: unsafe fn(*const ()),
 }
 
 // ===== impl Memory ======
@@ -110,9 +133,15 @@ where
     F: Future<Output = T>,
 {
     const RAW_WAKER_VTABLE: RawWakerVTable =
-        RawWakerVTable::new(Self::clone_waker, Self::wake, Self::wake, Self::drop_waker);
-
-    pub fn new(memory: &Memory<F, T>, future: F) -> RawTask<F, T> {
+        RawWakerVTable::new(Self::clone_waker, Self::wake, Self::wake_by_ref, Self::drop_waker);
+
+    pub fn new(
+        memory: &Memory<F, T>,
+        future: F,
+        name: Option<&'static str>,
+        slot: usize,
+        storage: Storage,
+    ) -> RawTask<F, T> {
         let ptr = memory as *const _ as *mut ();
 
         let task = Task::new(unsafe { NonNull::new_unchecked(ptr) });
@@ -129,7 +158,16 @@ where
                 schedule_timer: Self::schedule_timer,
                 get_output: Self::get_output,
                 drop_join_handle: Self::drop_join_handle,
+                abort: Self::abort,
             },
+            name,
+            slot,
+            stats: Stats::default(),
+            relative_deadline: None,
+            deadline: None,
+            next_deadline: None,
+            locals: None,
+            storage,
         };
 
         // NOTE: The scheduler is written when a task is spawned
@@ -157,30 +195,61 @@ where
         }
     }
 
+    /// Every waker holds a reference so the task outlives it
     unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
+        let raw = Self::from_ptr(ptr);
+        raw.memory().mut_header().state.ref_incr();
         RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE)
     }
 
-    unsafe fn drop_waker(_: *const ()) {
-        // no op
+    unsafe fn drop_waker(ptr: *const ()) {
+        Self::from_ptr(ptr).drop_ref();
     }
 
-    /// Wakes the task
+    /// Wakes the task, consuming the waker
     unsafe fn wake(ptr: *const ()) {
+        Self::wake_by_ref(ptr);
+        Self::drop_waker(ptr);
+    }
+
+    /// Wakes the task
+    unsafe fn wake_by_ref(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
         let memory = raw.memory();
         let header = memory.mut_header();
 
         let task = memory.task();
+        // The task is already in the queue or will never run again
+        if header.state.is_scheduled() || header.state.is_complete() {
+            defmt::trace!("{}: Already scheduled or complete", task.id);
+            return;
+        }
+
         defmt::trace!("{}: Waking raw task", task.id);
+        // Woken from an interrupt while the runtime sleeps on exit
+        crate::runtime::idle::resume();
+        memory.rt.get().as_ref().metrics.incr_wakeups();
 
         header.state.transition_to_scheduled();
+        hooks::emit(|hooks| hooks.on_wake(&task.info()));
         Self::schedule(ptr);
     }
 
+    /// Push the task onto the task queue. It is marked scheduled so that
+    /// neither a wakeup nor a timer queues it a second time
     unsafe fn schedule(ptr: *const ()) {
         let raw = Self::from_ptr(ptr);
         let memory = raw.memory();
+        let header = memory.mut_header();
+
+        header.state.set_scheduled();
+
+        let now = Instant::now();
+        header.stats.record_scheduled(now);
+        header.deadline = header
+            .next_deadline
+            .take()
+            .or_else(|| header.relative_deadline.map(|deadline| now + deadline));
 
         let task = NonNull::new_unchecked(memory.task() as *const _ as *mut Task);
         let mut rt = memory.rt.get();
@@ -192,6 +261,14 @@ where
         let memory = raw.memory();
         let header = memory.mut_header();
 
+        // The task is already in the timer queue. Keep the earliest deadline
+        if let Some(expiry) = header.expiry {
+            if deadline < expiry {
+                header.expiry = Some(deadline);
+            }
+            return;
+        }
+
         header.expiry = Some(deadline);
 
         let task = NonNull::new_unchecked(memory.task() as *const _ as *mut Task);
@@ -205,30 +282,126 @@ where
         let memory = raw.memory();
         let header = memory.mut_header();
 
-        let waker = Waker::from_raw(RawWaker::new(ptr, &Self::RAW_WAKER_VTABLE));
+        // The waker borrows the runtime's reference, so it is never dropped
+        let waker = ManuallyDrop::new(Waker::from_raw(RawWaker::new(
+            ptr,
+            &Self::RAW_WAKER_VTABLE,
+        )));
         let cx = &mut Context::from_waker(&waker);
 
         header.state.transition_to_running();
+        hooks::emit(|hooks| hooks.on_poll(&header.task.info()));
+
+        let start = Instant::now();
+        header.stats.record_run(start);
 
         let status = memory.mut_status();
-        match Self::poll_inner(status, cx) {
+        let res = {
+            let _budget = coop::budget();
+            Self::poll_inner(status, cx)
+        };
+
+        let end = Instant::now();
+        let elapsed = end - start;
+        header.stats.record_poll(elapsed);
+        if let Some(threshold) = memory.rt.get().as_ref().long_poll_threshold() {
+            if elapsed > threshold {
+                defmt::warn!(
+                    "{} ({}): Long poll of {}. Threshold is {}",
+                    header.task.id,
+                    header.name.unwrap_or("<unnamed>"),
+                    elapsed,
+                    threshold
+                );
+            }
+        }
+
+        // The deadline has been served unless the task was woken while running
+        if let Some(deadline) = header.deadline {
+            if end > deadline {
+                defmt::warn!(
+                    "{} ({}): Missed deadline by {}",
+                    header.task.id,
+                    header.name.unwrap_or("<unnamed>"),
+                    end - deadline
+                );
+                hooks::emit(|hooks| hooks.on_deadline_miss(&header.task.info()));
+            }
+            if !header.state.is_scheduled() {
+                header.deadline = None;
+            }
+        }
+
+        match res {
+            // The task aborted itself while running. The poll ends before
+            // the task is stopped
+            Poll::Pending if header.state.is_cancelled() => {
+                defmt::trace!("Task cancelled");
+                hooks::emit(|hooks| hooks.on_idle(&header.task.info()));
+                *status = Status::Consumed;
+                raw.finish();
+            }
             Poll::Pending => {
                 defmt::trace!("Task pending");
                 header.state.transition_to_idle();
+                hooks::emit(|hooks| hooks.on_idle(&header.task.info()));
             }
-            Poll::Ready(_) => {
-                header.state.transition_to_complete();
+            Poll::Ready(_) => raw.finish(),
+        }
+    }
 
-                if header.state.has_join_handle() {
-                    if header.state.has_join_waker() {
-                        header.wake_join_handle();
-                    }
-                } else {
-                    // Drop the future or output by replacing it with Consumed
-                    status.drop_future_or_output();
-                }
-            }
+    /// Mark the task complete, take it out of the runtime and notify its
+    /// join handle. The runtime's reference to the task is dropped
+    unsafe fn finish(&self) {
+        let memory = self.memory();
+        let header = memory.mut_header();
+
+        header.state.transition_to_complete();
+        if header.state.is_cancelled() {
+            hooks::emit(|hooks| hooks.on_abort(&header.task.info()));
+        } else {
+            hooks::emit(|hooks| hooks.on_complete(&header.task.info()));
+        }
+
+        // The task is no longer live, remove it from the registry and
+        // from any queue it is still in
+        let task = NonNull::new_unchecked(memory.task() as *const _ as *mut Task);
+        let mut rt = memory.rt.get();
+        rt.as_mut().registry.remove(task);
+        if header.state.is_scheduled() {
+            rt.as_mut().tasks.remove(task);
+            header.state.unset_scheduled();
+        }
+        if header.expiry.is_some() {
+            rt.as_mut().timers.remove(task);
+        }
+
+        if header.state.has_join_handle() && header.state.has_join_waker() {
+            header.wake_join_handle();
+        }
+
+        self.drop_ref();
+    }
+
+    /// Cancel the task, dropping its future. A task cancelling itself is
+    /// stopped once its current poll returns
+    unsafe fn abort(ptr: *const ()) {
+        let raw = Self::from_ptr(ptr);
+        let memory = raw.memory();
+        let header = memory.mut_header();
+
+        if header.state.is_complete() {
+            return;
+        }
+
+        defmt::debug!("{}: Aborting", header.task.id);
+        header.state.set_cancelled();
+        if header.state.is_running() {
+            return;
         }
+
+        *memory.mut_status() = Status::Consumed;
+        raw.finish();
     }
 
     fn poll_inner(status: &mut Status<F, T>, cx: &mut Context) -> Poll<()> {
@@ -245,18 +418,24 @@ where
         Poll::Ready(())
     }
 
-    unsafe fn get_output(ptr: *const (), dst: *mut ()) {
+    /// Move the output of a complete task into `dst`, a `Poll<T>`
+    unsafe fn get_output(ptr: *const (), dst: *mut ()) -> Result<(), JoinError> {
         let raw = Self::from_ptr(ptr);
         let memory = raw.memory();
         let status = memory.mut_status();
         let dst = dst as *mut Poll<F::Output>;
-        // TODO: Improve error handling
-        match mem::replace(status, Status::Consumed) {
-            Status::Finished(output) => {
-                *dst = Poll::Ready(output);
-            }
-            _ => panic!("Could not retrieve output!"),
+
+        if !matches!(status, Status::Finished(_)) {
+            return match memory.header().state.is_cancelled() {
+                true => Err(JoinError::Cancelled),
+                false => Err(JoinError::Consumed),
+            };
+        }
+
+        if let Status::Finished(output) = mem::replace(status, Status::Consumed) {
+            *dst = Poll::Ready(output);
         }
+        Ok(())
     }
 
     unsafe fn drop_join_handle(ptr: *const ()) {
@@ -265,6 +444,40 @@ where
         let header = memory.mut_header();
         // unset join handle bit
         header.state.unset_join_handle();
+        raw.drop_ref();
+    }
+
+    /// Drop a reference to the task, releasing it if it was the last one
+    unsafe fn drop_ref(&self) {
+        if self.memory().mut_header().state.ref_decr() {
+            self.release();
+        }
+    }
+
+    /// Drop the future or output of a complete task and return its memory
+    /// so it can be reused. Only called once the last reference is gone
+    unsafe fn release(&self) {
+        let memory = self.memory();
+        let header = memory.mut_header();
+        defmt::trace!("{}: Releasing memory", header.task.id);
+
+        match header.storage {
+            Storage::Pool => {
+                header.waker = None;
+                *memory.mut_status() = Status::Stopped;
+            }
+            Storage::Arena(arena) => {
+                memory.status.drop_in_place();
+                memory.header.drop_in_place();
+                arena.free(NonNull::new_unchecked(self.ptr as *mut u8));
+            }
+            #[cfg(feature = "alloc")]
+            Storage::Heap => {
+                memory.status.drop_in_place();
+                memory.header.drop_in_place();
+                drop(Box::from_raw(self.ptr as *mut Memory<F, T>));
+            }
+        }
     }
 }
 
@@ -276,19 +489,90 @@ where
 {
     pub fn new(memory: &'static [Memory<F, T>], future: impl FnOnce() -> F) -> Permit<F, T> {
         Permit {
-            memory,
+            source: Source::Pool(memory),
             future: future(),
+            name: None,
+            deadline: None,
         }
     }
-    pub fn acquire(self) -> Result<(&'static Memory<F, T>, F), SpawnError> {
-        for m in self.memory {
-            match unsafe { m.status.as_ref() } {
-                Status::Stopped => return Ok((m, self.future)),
-                _ => continue,
+
+    /// A permit that allocates memory for the task on the heap. The memory
+    /// is freed once the task completes and its output is no longer needed
+    #[cfg(feature = "alloc")]
+    pub fn boxed(future: F) -> Permit<F, T> {
+        Permit {
+            source: Source::Heap,
+            future,
+            name: None,
+            deadline: None,
+        }
+    }
+
+    /// A permit that draws memory for the task from the shared task arena,
+    /// installed through [`set_arena`](crate::task::set_arena)
+    pub fn from_arena(future: impl FnOnce() -> F) -> Permit<F, T> {
+        Permit {
+            source: Source::Arena,
+            future: future(),
+            name: None,
+            deadline: None,
+        }
+    }
+
+    /// Give the task a name. It is reported when introspecting the runtime
+    pub fn name(mut self, name: &'static str) -> Permit<F, T> {
+        self.name = Some(name);
+        self
+    }
+
+    /// Give the task a deadline relative to each time it is scheduled. It
+    /// orders tasks when the runtime schedules earliest deadline first
+    pub fn deadline(mut self, deadline: Duration) -> Permit<F, T> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Find free memory for the task, returning it with its slot and where
+    /// it comes from. Memory from an arena is only returned once the task
+    /// it is given to completes
+    pub(crate) fn acquire(&self) -> Result<(&'static Memory<F, T>, usize, Storage), SpawnError> {
+        match self.source {
+            Source::Pool(pool) => {
+                for (slot, m) in pool.iter().enumerate() {
+                    match unsafe { m.status.as_ref() } {
+                        Status::Stopped => return Ok((m, slot, Storage::Pool)),
+                        _ => continue,
+                    }
+                }
+
+                Err(SpawnError::QueueFull)
+            }
+            Source::Arena => {
+                let (ptr, slot, arena) =
+                    arena::alloc(Layout::new::<Memory<F, T>>()).ok_or(SpawnError::NoMemory)?;
+
+                let ptr = ptr.as_ptr() as *mut Memory<F, T>;
+                unsafe {
+                    ptr.write(Memory::alloc());
+                    Ok((&*ptr, slot, Storage::Arena(arena)))
+                }
+            }
+            #[cfg(feature = "alloc")]
+            Source::Heap => {
+                let memory = Box::leak(Box::new(Memory::alloc()));
+                Ok((memory, 0, Storage::Heap))
             }
         }
+    }
+
+    /// Acquire memory for the task and initialise it
+    pub(crate) fn into_raw(self) -> Result<RawTask<F, T>, SpawnError> {
+        let (memory, slot, storage) = self.acquire()?;
 
-        Err(SpawnError::QueueFull)
+        let raw = RawTask::new(memory, self.future, self.name, slot, storage);
+        unsafe { memory.mut_header().relative_deadline = self.deadline };
+
+        Ok(raw)
     }
 }
 
@@ -309,8 +593,149 @@ where
 
         // if res.is_ready() { self.drop_future_or_output() }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::future::{pending, Pending};
+    use std::boxed::Box;
+
+    type Mem = Memory<Pending<()>, ()>;
+    type Raw = RawTask<Pending<()>, ()>;
+
+    fn runtime() -> &'static Runtime {
+        Box::leak(Box::new(Runtime::new()))
+    }
+
+    // Set up a task as if it was spawned, without putting it in the task queue
+    fn spawn(rt: &'static Runtime) -> &'static Mem {
+        let memory: &'static Mem = Box::leak(Box::new(Memory::alloc()));
+        RawTask::new(memory, pending(), None, 0, Storage::Pool);
+        memory.rt.replace(NonNull::from(rt));
+        unsafe {
+            memory.mut_header().state.unset_scheduled();
+            memory.rt.get().as_mut().registry.push_back(memory.task().as_ptr());
+        }
+        memory
+    }
+
+    // Put the task in the task queue without going through `schedule`,
+    // which reads the clock
+    fn enqueue(memory: &Mem) {
+        unsafe {
+            memory.mut_header().state.set_scheduled();
+            memory.rt.get().as_mut().tasks.push_back(memory.task().as_ptr());
+        }
+    }
+
+    fn ptr(memory: &Mem) -> *const () {
+        memory as *const _ as *const ()
+    }
+
+    fn at(millis: u32) -> Instant {
+        Instant::from_millis(millis)
+    }
+
+    #[test]
+    fn schedule_timer_keeps_earliest_deadline() {
+        let rt = runtime();
+        let memory = spawn(rt);
+        let task = Some(memory.task().as_ptr());
+
+        unsafe {
+            Raw::schedule_timer(ptr(memory), at(10));
+            Raw::schedule_timer(ptr(memory), at(5));
+            Raw::schedule_timer(ptr(memory), at(20));
+        }
+
+        assert_eq!(memory.task().expiry(), Some(at(5)));
+        // Queued only once
+        assert_eq!(rt.timers.head.get(), task);
+        assert_eq!(rt.timers.tail.get(), task);
+    }
+
+    #[test]
+    fn fired_timer_does_not_requeue_scheduled_task() {
+        let rt = runtime();
+        let memory = spawn(rt);
+        let task = memory.task().as_ptr();
+
+        enqueue(memory);
+        unsafe { Raw::schedule_timer(ptr(memory), at(5)) };
+
+        assert_eq!(rt.timers.process(at(10)), 1);
+        assert!(rt.timers.head.get().is_none());
+        rt.tasks.prepare();
+        assert_eq!(rt.tasks.pop_front().map(|t| t.as_ptr()), Some(task));
+        assert!(rt.tasks.is_empty());
+    }
+
+    #[test]
+    fn abort_removes_task_from_queues() {
+        let rt = runtime();
+        let tasks = [spawn(rt), spawn(rt), spawn(rt)];
+        for memory in tasks {
+            unsafe { Raw::schedule_timer(ptr(memory), at(5)) };
+        }
+        let middle = tasks[1];
+        enqueue(middle);
+        unsafe { Raw::abort(ptr(middle)) };
+
+        let state = middle.header().state;
+        assert!(state.is_complete() && state.is_cancelled());
+        assert!(!state.is_scheduled());
+        assert!(rt.tasks.is_empty());
+        assert_eq!(middle.task().expiry(), None);
+
+        // The other timers are still linked
+        let (first, last) = (tasks[0].task(), tasks[2].task());
+        assert_eq!(rt.timers.head.get(), Some(first.as_ptr()));
+        assert_eq!(first.timers.next(), Some(last.as_ptr()));
+        assert_eq!(last.timers.prev(), Some(first.as_ptr()));
+        assert_eq!(rt.timers.tail.get(), Some(last.as_ptr()));
+
+        // The join handle holds the last reference
+        assert_eq!(state.ref_count(), 1);
+        unsafe { Raw::drop_join_handle(ptr(middle)) };
+        assert!(matches!(unsafe { middle.status.as_ref() }, Status::Stopped));
+    }
+
+    #[test]
+    fn abort_timer_parked_task() {
+        let rt = runtime();
+        let memory = spawn(rt);
+        unsafe {
+            Raw::schedule_timer(ptr(memory), at(5));
+            Raw::abort(ptr(memory));
+        }
+
+        assert!(memory.header().state.is_complete());
+        assert!(rt.timers.head.get().is_none());
+        assert!(rt.tasks::<1>().is_empty());
+        assert!(matches!(unsafe { memory.status.as_ref() }, Status::Consumed));
+    }
+
+    #[test]
+    fn self_abort_waits_for_poll_to_return() {
+        let rt = runtime();
+        let memory = spawn(rt);
+        unsafe {
+            memory.mut_header().state.transition_to_running();
+            Raw::abort(ptr(memory));
+        }
+
+        // The future is still being polled so it is left in place
+        let state = memory.header().state;
+        assert!(state.is_cancelled() && state.is_running());
+        assert!(!state.is_complete());
+        assert!(matches!(unsafe { memory.status.as_ref() }, Status::Running(_)));
+        assert_eq!(rt.tasks::<1>().len(), 1);
 
-    fn drop_future_or_output(&mut self) {
-        *self = Status::Consumed
+        // Aborting again does nothing
+        unsafe { Raw::abort(ptr(memory)) };
+        assert!(!memory.header().state.is_complete());
     }
 }
@@ -8,3 +8,12 @@ pub fn spawn<F: Future<Output = T>, T>(permit: Permit<F, T>) -> Result<JoinHandl
     let spawner = context::spawner();
     spawner.spawn(permit)
 }
+
+/// Spawn a task stored on the heap. Its memory is freed once it completes
+/// and its output is no longer needed
+#[cfg(feature = "alloc")]
+pub fn spawn_boxed<F: Future<Output = T> + 'static, T: 'static>(
+    future: F,
+) -> Result<JoinHandle<T>, SpawnError> {
+    spawn(Permit::boxed(future))
+}
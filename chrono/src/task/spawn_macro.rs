@@ -0,0 +1,47 @@
+/// Spawn an async block onto the current runtime, generating static storage
+/// for it inline. `size` is the number of instances that can be live at once
+/// and defaults to 1. The task is named after the `name` argument, or else
+/// after where it was spawned, as in `echo::server:42`. `#[chrono::alloc]`
+/// tasks are likewise named after their function by default
+///
+/// ```ignore
+/// let value = 5;
+/// let handle = chrono::spawn!(size = 2, async move { value * 2 }).unwrap();
+/// ```
+///
+/// Returns the same `Result` as [`spawn`](crate::task::spawn). Like
+/// `#[chrono::alloc]`, it needs `#![feature(type_alias_impl_trait)]`
+#[macro_export]
+macro_rules! spawn {
+    (size = $size:expr, name = $name:expr, $future:expr $(,)?) => {
+        $crate::spawn!(@permit $size, $future, $name)
+    };
+
+    (size = $size:expr, $future:expr $(,)?) => {
+        $crate::spawn!(
+            @permit $size,
+            $future,
+            ::core::concat!(::core::module_path!(), ":", ::core::line!())
+        )
+    };
+
+    (name = $name:expr, $future:expr $(,)?) => {
+        $crate::spawn!(size = 1, name = $name, $future)
+    };
+
+    (@permit $size:expr, $future:expr, $name:expr) => {{
+        type __T = impl ::core::marker::Sized;
+        type __F = impl ::core::future::Future<Output = __T>;
+
+        const ALLOC: $crate::task::Memory<__F, __T> = $crate::task::Memory::alloc();
+        static MEMORY: [$crate::task::Memory<__F, __T>; $size] = [ALLOC; $size];
+
+        let permit: $crate::task::Permit<__F, __T> =
+            $crate::task::Permit::new(&MEMORY, move || $future);
+        $crate::task::spawn(permit.name($name))
+    }};
+
+    ($future:expr $(,)?) => {
+        $crate::spawn!(size = 1, $future)
+    };
+}
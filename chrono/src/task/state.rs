@@ -15,9 +15,20 @@ const JOIN_HANDLE: usize = 1 << 3;
 // The waker belonging to the join handle is registered
 const JOIN_WAKER: usize = 1 << 4;
 
-// Initial state of a task
-const INITIAL_STATE: usize = SCHEDULED | JOIN_HANDLE;
+// The task has been cancelled
+const CANCELLED: usize = 1 << 5;
 
+// One reference to the task. The bits from here up count the references
+const REF_ONE: usize = 1 << 6;
+
+// Bits used for the flags rather than the reference count
+const FLAGS_MASK: usize = REF_ONE - 1;
+
+// Initial state of a task. One reference is held by the runtime until the
+// task completes and one by the join handle
+const INITIAL_STATE: usize = SCHEDULED | JOIN_HANDLE | (REF_ONE * 2);
+
+#[derive(Clone, Copy)]
 pub struct State {
     pub state: usize,
     task_id: Option<TaskId>,
@@ -39,7 +50,7 @@ impl State {
         }
     }
 
-    pub fn has_join_handle(&mut self) -> bool {
+    pub fn has_join_handle(&self) -> bool {
         self.state & JOIN_HANDLE == JOIN_HANDLE
     }
 
@@ -75,6 +86,35 @@ impl State {
         self.state &= !SCHEDULED;
     }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.state & CANCELLED == CANCELLED
+    }
+
+    pub fn set_cancelled(&mut self) {
+        self.state |= CANCELLED;
+    }
+
+    /// Number of references to the task. Its memory is released once there
+    /// are none left
+    pub fn ref_count(&self) -> usize {
+        (self.state & !FLAGS_MASK) / REF_ONE
+    }
+
+    pub fn ref_incr(&mut self) {
+        self.state += REF_ONE;
+    }
+
+    /// Drop a reference. Returns whether it was the last one
+    pub fn ref_decr(&mut self) -> bool {
+        debug_assert!(self.ref_count() > 0);
+        self.state -= REF_ONE;
+        self.ref_count() == 0
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state & RUNNING == RUNNING
+    }
+
     pub fn set_running(&mut self) {
         self.state |= RUNNING;
     }
@@ -103,17 +143,18 @@ impl State {
         }
     }
 
+    /// The scheduled bit is left as is since the task may have been woken
+    /// while it was running
     pub fn transition_to_idle(&mut self) {
         self.unset_running();
-        self.unset_scheduled();
         if let Some(task_id) = self.task_id {
             defmt::trace!("{}: Transitioned to idle. State: {}", task_id, self);
         }
     }
 
+    /// A task woken while it is running stays running until its poll returns
     pub fn transition_to_scheduled(&mut self) {
         self.set_scheduled();
-        self.unset_running();
         if let Some(task_id) = self.task_id {
             defmt::trace!(
                 "{}: Transitioned to scheduled. State: {}",
@@ -126,16 +167,18 @@ impl State {
 
 impl core::fmt::Display for State {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        // scheduled | running | complete | join handle | join waker | ref count
+        // scheduled | running | complete | join handle | join waker | cancelled | refs
         let scheduled = self.is_scheduled();
-        let running = self.state & RUNNING == RUNNING;
+        let running = self.is_running();
         let complete = self.is_complete();
-        let join_handle = self.state & JOIN_HANDLE == JOIN_HANDLE;
+        let join_handle = self.has_join_handle();
         let join_waker = self.has_join_waker();
+        let cancelled = self.is_cancelled();
+        let refs = self.ref_count();
         write!(
             f,
-            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}}}",
-            scheduled, running, complete, join_handle, join_waker
+            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}, cancelled={}, refs={}}}",
+            scheduled, running, complete, join_handle, join_waker, cancelled, refs
         )
     }
 }
@@ -143,14 +186,16 @@ impl core::fmt::Display for State {
 impl defmt::Format for State {
     fn format(&self, f: defmt::Formatter) {
         let scheduled = self.is_scheduled();
-        let running = self.state & RUNNING == RUNNING;
+        let running = self.is_running();
         let complete = self.is_complete();
-        let join_handle = self.state & JOIN_HANDLE == JOIN_HANDLE;
+        let join_handle = self.has_join_handle();
         let join_waker = self.has_join_waker();
+        let cancelled = self.is_cancelled();
+        let refs = self.ref_count();
         defmt::write!(
             f,
-            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}}}",
-            scheduled, running, complete, join_handle, join_waker
+            "State {{ scheduled={}, running={}, complete={}, has_join_handle={}, has_join_waker={}, cancelled={}, refs={}}}",
+            scheduled, running, complete, join_handle, join_waker, cancelled, refs
         )
     }
 }
@@ -178,4 +223,28 @@ mod tests {
         state.ref_decr();
         assert_eq!(state.ref_count(), 1);
     }
+
+    #[test]
+    fn flags_keep_ref_count() {
+        let mut state = State::new();
+        state.transition_to_running();
+        state.set_cancelled();
+        state.transition_to_complete();
+        state.unset_join_handle();
+        assert_eq!(state.ref_count(), 2);
+        assert!(!state.ref_decr());
+        assert!(state.ref_decr());
+    }
+
+    #[test]
+    fn woken_while_running_stays_running() {
+        let mut state = State::new();
+        state.transition_to_running();
+        state.transition_to_scheduled();
+        assert!(state.is_running() && state.is_scheduled());
+
+        // Still scheduled once the poll returns, so it runs again
+        state.transition_to_idle();
+        assert!(!state.is_running() && state.is_scheduled());
+    }
 }
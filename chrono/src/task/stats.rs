@@ -0,0 +1,69 @@
+use crate::time::{Duration, Instant, TICKS_PER_SECOND};
+
+/// Poll statistics of a task. Totals are kept in ticks so they don't
+/// overflow over the lifetime of the program
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    /// Number of times the task has been polled
+    pub polls: u64,
+    /// Total ticks spent polling the task
+    pub poll_ticks: u64,
+    /// Longest single poll of the task
+    pub max_poll: Duration,
+    /// Total ticks the task spent scheduled before it was run
+    pub scheduled_ticks: u64,
+    /// When the task was last scheduled. Cleared once it is run
+    pub(crate) scheduled_at: Option<Instant>,
+}
+
+// ===== impl Stats =====
+
+impl Stats {
+    pub(crate) fn record_scheduled(&mut self, now: Instant) {
+        // A task can be scheduled multiple times before it runs. We only
+        // care about the first time
+        if self.scheduled_at.is_none() {
+            self.scheduled_at = Some(now);
+        }
+    }
+
+    pub(crate) fn record_run(&mut self, now: Instant) {
+        if let Some(scheduled_at) = self.scheduled_at.take() {
+            let waited = now - scheduled_at;
+            self.scheduled_ticks = self.scheduled_ticks.saturating_add(waited.ticks() as u64);
+        }
+    }
+
+    pub(crate) fn record_poll(&mut self, elapsed: Duration) {
+        self.polls = self.polls.saturating_add(1);
+        self.poll_ticks = self.poll_ticks.saturating_add(elapsed.ticks() as u64);
+        if elapsed > self.max_poll {
+            self.max_poll = elapsed;
+        }
+    }
+
+    /// Average duration of a poll
+    pub fn mean_poll(&self) -> Duration {
+        match self.polls {
+            0 => Duration::default(),
+            n => Duration::new((self.poll_ticks / n) as u32),
+        }
+    }
+}
+
+impl defmt::Format for Stats {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Stats {{ polls={}, poll_time={} ms, max_poll={}, scheduled_time={} ms }}",
+            self.polls,
+            ticks_to_millis(self.poll_ticks),
+            self.max_poll,
+            ticks_to_millis(self.scheduled_ticks)
+        )
+    }
+}
+
+fn ticks_to_millis(ticks: u64) -> u64 {
+    ticks * 1000 / TICKS_PER_SECOND as u64
+}
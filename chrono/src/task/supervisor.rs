@@ -0,0 +1,165 @@
+use core::future::Future;
+
+use super::join::JoinError;
+use super::raw::Permit;
+use crate::runtime::SpawnError;
+use crate::time::{self, Duration};
+
+/// Sleep before the first restart, unless the policy or
+/// [`Supervisor::backoff`] gives another
+const DEFAULT_BACKOFF_MILLIS: u32 = 100;
+
+/// The sleep before a restart stops doubling once it reaches this
+const MAX_BACKOFF_SECS: u32 = 10;
+
+/// When a supervisor restarts its task
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RestartPolicy {
+    /// Restart whenever the task finishes
+    Always,
+    /// Restart only when the task returns an error
+    OnError,
+    /// Restart when the task returns an error, at most `max` times, with
+    /// `backoff` as the first sleep before a restart
+    MaxRestarts { max: u32, backoff: Duration },
+}
+
+/// The output of a supervised task
+pub trait Outcome {
+    /// Whether the task failed
+    fn is_err(&self) -> bool;
+}
+
+/// Why a supervisor stopped without an output from its task
+#[derive(Debug)]
+pub enum SupervisorError {
+    /// The task could not be spawned
+    Spawn(SpawnError),
+    /// The task ended without an output and was not restarted
+    Join(JoinError),
+}
+
+/// Spawns a task and spawns it again whenever it finishes, as allowed by its
+/// [`RestartPolicy`]. The task is created by a function returning a
+/// [`Permit`], typically a `#[chrono::alloc]` function, whose pool the
+/// supervisor reuses for every restart.
+///
+/// The supervisor sleeps before each restart. The sleep doubles every time,
+/// up to 10 seconds, so a task failing right away does not hog the executor
+///
+/// ```ignore
+/// let supervisor = Supervisor::new(RestartPolicy::OnError, || mqtt(config));
+/// supervisor.run().await
+/// ```
+pub struct Supervisor<P, F, T>
+where
+    P: FnMut() -> Permit<F, T>,
+    F: Future<Output = T> + 'static,
+    T: Outcome + 'static,
+{
+    permit: P,
+    policy: RestartPolicy,
+    /// Sleep before the first restart of a run
+    backoff: Duration,
+    restarts: u32,
+}
+
+// ===== impl Supervisor =====
+
+impl<P, F, T> Supervisor<P, F, T>
+where
+    P: FnMut() -> Permit<F, T>,
+    F: Future<Output = T> + 'static,
+    T: Outcome + 'static,
+{
+    pub fn new(policy: RestartPolicy, permit: P) -> Supervisor<P, F, T> {
+        let backoff = match policy {
+            RestartPolicy::MaxRestarts { backoff, .. } => backoff,
+            _ => Duration::from_millis(DEFAULT_BACKOFF_MILLIS),
+        };
+
+        Supervisor {
+            permit,
+            policy,
+            backoff,
+            restarts: 0,
+        }
+    }
+
+    /// Set the sleep before the first restart. Defaults to the backoff of
+    /// [`RestartPolicy::MaxRestarts`], or 100 ms for the other policies
+    pub fn backoff(mut self, backoff: Duration) -> Supervisor<P, F, T> {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Number of times the task has been restarted during the current or
+    /// last run
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Run the task until the policy stops restarting it. Returns its last
+    /// output, or an error if it could not be spawned or ended without an
+    /// output. A task ending without an output counts as failed
+    pub async fn run(&mut self) -> Result<T, SupervisorError> {
+        // Every run starts over with a fresh restart budget and backoff
+        self.restarts = 0;
+        let mut backoff = self.backoff;
+        let max_backoff = Duration::from_secs(MAX_BACKOFF_SECS);
+
+        loop {
+            let output = crate::task::spawn((self.permit)())?.await;
+            let failed = match &output {
+                Ok(output) => output.is_err(),
+                Err(_) => true,
+            };
+
+            let restart = match self.policy {
+                RestartPolicy::Always => true,
+                RestartPolicy::OnError => failed,
+                RestartPolicy::MaxRestarts { max, .. } => failed && self.restarts < max,
+            };
+            if !restart {
+                return output.map_err(SupervisorError::Join);
+            }
+
+            self.restarts += 1;
+            defmt::warn!(
+                "Supervised task finished (error: {}). Restart {}",
+                failed,
+                self.restarts
+            );
+
+            time::sleep(backoff).await;
+            if backoff < max_backoff {
+                backoff = backoff + backoff;
+                if backoff > max_backoff {
+                    backoff = max_backoff;
+                }
+            }
+        }
+    }
+}
+
+// ===== impl SupervisorError =====
+
+impl From<SpawnError> for SupervisorError {
+    fn from(e: SpawnError) -> SupervisorError {
+        SupervisorError::Spawn(e)
+    }
+}
+
+// ===== impl Outcome =====
+
+impl Outcome for () {
+    fn is_err(&self) -> bool {
+        false
+    }
+}
+
+impl<T, E> Outcome for Result<T, E> {
+    fn is_err(&self) -> bool {
+        Result::is_err(self)
+    }
+}
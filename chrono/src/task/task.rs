@@ -6,6 +6,8 @@ use crate::runtime::queue::Generation;
 use crate::time::instant::Instant;
 
 use super::header::Header;
+use super::info::TaskInfo;
+use super::local::Entry;
 
 #[derive(Clone, Copy)]
 pub struct Task {
@@ -14,6 +16,7 @@ pub struct Task {
     pub(crate) generation: Generation,
     pub(crate) tasks: Pointers,
     pub(crate) timers: Pointers,
+    pub(crate) registry: Pointers,
 }
 
 #[derive(Clone, Copy, defmt::Format)]
@@ -36,6 +39,7 @@ impl Task {
             generation: Generation(1),
             tasks: Pointers::default(),
             timers: Pointers::default(),
+            registry: Pointers::default(),
         }
     }
 
@@ -56,6 +60,15 @@ impl Task {
         unsafe { ((*header).vtable.schedule)(ptr) }
     }
 
+    /// Schedule the task unless it is already in the task queue
+    pub(crate) fn schedule_if_idle(&self) {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &*(ptr as *const Header) };
+        if !header.state.is_scheduled() {
+            self.schedule()
+        }
+    }
+
     pub fn is_timer_complete(&self, now: Instant) -> bool {
         let ptr = self.raw.as_ptr();
         let header = unsafe { &*(ptr as *const Header) };
@@ -79,6 +92,48 @@ impl Task {
         header.expiry = None;
     }
 
+    /// A snapshot of the task's current state
+    pub fn info(&self) -> TaskInfo {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &*(ptr as *const Header) };
+        TaskInfo {
+            id: self.id,
+            name: header.name,
+            state: header.state,
+            expiry: header.expiry,
+            slot: header.slot,
+            stats: header.stats,
+            deadline: header.deadline,
+        }
+    }
+
+    /// The instant the task should run by, if it has a deadline
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &*(ptr as *const Header) };
+        header.deadline
+    }
+
+    /// First of the task-local values set on the task
+    pub(crate) fn locals(&self) -> Option<NonNull<Entry>> {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &*(ptr as *const Header) };
+        header.locals
+    }
+
+    pub(crate) fn set_locals(&self, entry: Option<NonNull<Entry>>) {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &mut *(ptr as *mut Header) };
+        header.locals = entry;
+    }
+
+    /// Sets the deadline used the next time the task is scheduled
+    pub(crate) fn set_next_deadline(&self, deadline: Instant) {
+        let ptr = self.raw.as_ptr();
+        let header = unsafe { &mut *(ptr as *mut Header) };
+        header.next_deadline = Some(deadline);
+    }
+
     pub fn set_generation(&mut self, generation: Generation) {
         self.generation = generation
     }
@@ -103,7 +158,6 @@ impl Pointers {
         self.next = task;
     }
 
-    #[allow(dead_code)]
     pub(crate) fn set_prev(&mut self, task: Option<NonNull<Task>>) {
         self.prev = task;
     }
@@ -116,6 +170,10 @@ impl TaskId {
         static ID: Counter = Counter::new();
         TaskId(ID.incr())
     }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
 }
 
 impl Display for TaskId {
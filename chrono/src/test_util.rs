@@ -0,0 +1,26 @@
+//! Helpers shared by the unit tests
+
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// A waker setting the flag it points to
+pub(crate) fn waker(woken: &Cell<bool>) -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |p| RawWaker::new(p, &VTABLE),
+        |p| unsafe { (*(p as *const Cell<bool>)).set(true) },
+        |p| unsafe { (*(p as *const Cell<bool>)).set(true) },
+        |_| {},
+    );
+    let raw = RawWaker::new(woken as *const _ as *const (), &VTABLE);
+    unsafe { Waker::from_raw(raw) }
+}
+
+/// Poll a future once with a waker setting `woken`. The flag is cleared
+/// first so it only tells whether the future was woken since
+pub(crate) fn poll<F: Future + Unpin>(future: &mut F, woken: &Cell<bool>) -> Poll<F::Output> {
+    woken.set(false);
+    let waker = waker(woken);
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
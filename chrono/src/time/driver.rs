@@ -57,6 +57,8 @@ impl Driver {
             let mut inner = self.inner.as_ref().unwrap().borrow_mut();
             inner.timer.clear_event(Event::Update);
             inner.timer.stop();
+            // Return to the runtime if it is sleeping on exit
+            crate::runtime::idle::resume();
             cortex_m::asm::sev();
         })
     }
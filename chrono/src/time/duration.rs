@@ -1,10 +1,11 @@
 use core::cmp::{PartialEq, PartialOrd};
+use core::ops::Add;
 
 use smoltcp::time::Duration as SmoltcpDuration;
 
 use super::TICKS_PER_SECOND;
 
-#[derive(PartialEq, Eq, PartialOrd, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, Default, Debug)]
 pub struct Duration {
     ticks: u32,
 }
@@ -47,6 +48,21 @@ impl Duration {
     pub fn as_micros(&self) -> u32 {
         self.ticks
     }
+
+    /// Subtract `rhs`, returning a zero duration if it is longer than `self`
+    pub fn saturating_sub(&self, rhs: Duration) -> Duration {
+        Duration::new(self.ticks.saturating_sub(rhs.ticks))
+    }
+}
+
+impl Add<Duration> for Duration {
+    type Output = Duration;
+
+    // Saturate rather than overflow. Durations are used to accumulate
+    // statistics which can run for the lifetime of the program
+    fn add(self, rhs: Duration) -> Self::Output {
+        Duration::new(self.ticks.saturating_add(rhs.ticks))
+    }
 }
 
 impl defmt::Format for Duration {
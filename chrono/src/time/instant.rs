@@ -1,3 +1,5 @@
+use core::cell::Cell;
+use core::cmp::Ordering;
 use core::ops::{Add, Sub};
 
 use smoltcp::time::Instant as SmoltcpInstant;
@@ -6,7 +8,19 @@ use stm32f3xx_hal::pac::DWT;
 use super::duration::Duration;
 use super::TICKS_PER_SECOND;
 
-#[derive(PartialEq, Eq, PartialOrd, Clone, Copy, defmt::Format)]
+/// Ticks the clock is ahead of the cycle counter by. The counter doesn't
+/// run while the core is in Stop mode
+static OFFSET: Offset = Offset(Cell::new(0));
+
+struct Offset(Cell<u32>);
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Offset {}
+
+/// An instant in time, counted in ticks of the cycle counter. The counter
+/// wraps around, so instants are ordered by which comes first within half a
+/// period of each other
+#[derive(PartialEq, Eq, Clone, Copy, Debug, defmt::Format)]
 pub struct Instant {
     now: u32,
 }
@@ -17,7 +31,7 @@ impl Instant {
     }
     pub fn now() -> Instant {
         Instant {
-            now: DWT::cycle_count(),
+            now: DWT::cycle_count().wrapping_add(OFFSET.0.get()),
         }
     }
 
@@ -30,13 +44,45 @@ impl Instant {
     pub fn as_millis(&self) -> u32 {
         self.now / (TICKS_PER_SECOND / 1000)
     }
+
+    pub fn ticks(&self) -> u32 {
+        self.now
+    }
+
+    /// Time elapsed since `self`. Correct across a wrap of the counter
+    pub fn elapsed(&self) -> Duration {
+        Instant::now() - *self
+    }
+
+    /// The duration elapsed from `earlier` to `self`, or a zero duration if
+    /// `earlier` is later than `self`
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        match *self >= earlier {
+            true => *self - earlier,
+            false => Duration::new(0),
+        }
+    }
+}
+
+impl Ord for Instant {
+    fn cmp(&self, other: &Instant) -> Ordering {
+        (self.now.wrapping_sub(other.now) as i32).cmp(&0)
+    }
+}
+
+impl PartialOrd for Instant {
+    fn partial_cmp(&self, other: &Instant) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Sub<Instant> for Instant {
     type Output = Duration;
 
+    // The cycle counter wraps around every few minutes. Measuring across
+    // the wrap still gives the elapsed time as long as it is under a period
     fn sub(self, rhs: Instant) -> Self::Output {
-        let dur = self.now - rhs.now;
+        let dur = self.now.wrapping_sub(rhs.now);
         Duration::new(dur)
     }
 }
@@ -45,7 +91,7 @@ impl Add<Duration> for Instant {
     type Output = Instant;
 
     fn add(self, rhs: Duration) -> Self::Output {
-        let then = self.now + rhs.ticks();
+        let then = self.now.wrapping_add(rhs.ticks());
         Instant { now: then }
     }
 }
@@ -62,4 +108,39 @@ impl From<SmoltcpInstant> for Instant {
         let millis = instant.total_millis().try_into().unwrap();
         Instant::from_millis(millis) 
     }
-}
\ No newline at end of file
+}
+
+/// Move the clock forward by time that passed while the cycle counter was
+/// stopped
+pub(crate) fn advance(duration: Duration) {
+    OFFSET.0.set(OFFSET.0.get().wrapping_add(duration.ticks()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(now: u32) -> Instant {
+        Instant { now }
+    }
+
+    #[test]
+    fn ordering_across_wrap() {
+        let before = at(u32::MAX - 10);
+        let after = at(5);
+        assert!(before < after);
+        assert!(after > before);
+        assert_eq!(before.max(after), after);
+        assert_eq!(at(3).cmp(&at(3)), Ordering::Equal);
+    }
+
+    #[test]
+    fn arithmetic_across_wrap() {
+        let before = at(u32::MAX - 10);
+        let after = before + Duration::new(16);
+        assert_eq!(after, at(5));
+        assert_eq!(after - before, Duration::new(16));
+        assert_eq!(after.saturating_duration_since(before), Duration::new(16));
+        assert_eq!(before.saturating_duration_since(after), Duration::new(0));
+    }
+}
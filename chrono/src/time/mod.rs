@@ -6,8 +6,9 @@ pub use duration::Duration;
 
 pub(crate) mod instant;
 pub use instant::Instant;
+pub(crate) use instant::advance;
 
 mod sleep;
 pub use sleep::sleep;
 
-const TICKS_PER_SECOND: u32 = 1_000_000;
+pub(crate) const TICKS_PER_SECOND: u32 = 1_000_000;
@@ -0,0 +1,244 @@
+//! Records scheduling events into a fixed-size ring buffer. Install a
+//! [`Tracer`] as the runtime hooks and dump it over defmt or drain it into a
+//! byte buffer to send over TCP. The `chrono-trace` tool in `tools/` turns a
+//! dump into Chrome trace JSON that can be viewed in Perfetto.
+//!
+//! ```ignore
+//! static TRACER: Tracer<256> = Tracer::new();
+//! chrono::runtime::set_hooks(&TRACER);
+//! // ... later
+//! TRACER.dump();
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+
+use crate::runtime::RuntimeHooks;
+use crate::task::TaskInfo;
+use crate::time::Instant;
+
+/// Marker at the start of every event printed by [`Tracer::dump`]. Used by
+/// the decoder to find events in the defmt output
+pub const DUMP_MARKER: &str = "chrono-trace";
+
+/// Ring buffer of scheduling events. Once full, the oldest events are overwritten
+pub struct Tracer<const N: usize> {
+    events: UnsafeCell<[Event; N]>,
+    /// Index the next event is written to
+    head: Cell<usize>,
+    /// Number of events in the buffer
+    len: Cell<usize>,
+    /// Number of events overwritten before they were read
+    dropped: Cell<u32>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Event {
+    /// Ticks of the [`Instant`] the event happened at
+    pub timestamp: u32,
+    pub kind: EventKind,
+    /// Id of the task the event belongs to. Zero for events of the runtime
+    pub task: u64,
+    /// Name of the task. Only recorded on spawn
+    pub name: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum EventKind {
+    Spawn = 0,
+    PollStart = 1,
+    PollEnd = 2,
+    Wake = 3,
+    Timer = 4,
+    Complete = 5,
+    IdleEnter = 6,
+    IdleExit = 7,
+    Abort = 8,
+}
+
+// ===== impl Tracer =====
+
+impl<const N: usize> Tracer<N> {
+    /// Fails to compile for a tracer without room for any event
+    const NOT_EMPTY: () = assert!(N > 0, "a Tracer needs room for at least one event");
+
+    pub const fn new() -> Tracer<N> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NOT_EMPTY;
+
+        Tracer {
+            events: UnsafeCell::new([Event::EMPTY; N]),
+            head: Cell::new(0),
+            len: Cell::new(0),
+            dropped: Cell::new(0),
+        }
+    }
+
+    /// Number of events in the buffer
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of events overwritten before they were read
+    pub fn dropped(&self) -> u32 {
+        self.dropped.get()
+    }
+
+    pub fn clear(&self) {
+        self.len.replace(0);
+        self.dropped.replace(0);
+    }
+
+    pub fn record(&self, kind: EventKind, task: Option<&TaskInfo>) {
+        let event = Event {
+            timestamp: Instant::now().ticks(),
+            kind,
+            task: task.map(|t| t.id.as_u64()).unwrap_or(0),
+            name: match kind {
+                EventKind::Spawn => task.and_then(|t| t.name),
+                _ => None,
+            },
+        };
+
+        let head = self.head.get();
+        unsafe { (*self.events.get())[head] = event };
+        self.head.replace((head + 1) % N);
+
+        if self.len.get() == N {
+            self.dropped.replace(self.dropped.get().wrapping_add(1));
+        } else {
+            self.len.replace(self.len.get() + 1);
+        }
+    }
+
+    /// Remove the oldest event from the buffer
+    pub fn pop(&self) -> Option<Event> {
+        let len = self.len.get();
+        if len == 0 {
+            return None;
+        }
+
+        let tail = (self.head.get() + N - len) % N;
+        self.len.replace(len - 1);
+        Some(unsafe { (*self.events.get())[tail] })
+    }
+
+    /// Print every event in the buffer over defmt, oldest first, and
+    /// empty the buffer
+    pub fn dump(&self) {
+        if self.dropped() > 0 {
+            defmt::warn!("{}: {} events dropped", DUMP_MARKER, self.dropped());
+        }
+
+        while let Some(event) = self.pop() {
+            defmt::info!(
+                "chrono-trace {=u32} {=u8} {=u64} {=str}",
+                event.timestamp,
+                event.kind as u8,
+                event.task,
+                event.name.unwrap_or("")
+            );
+        }
+        self.clear();
+    }
+
+    /// Encode as many events as fit into `buf`, oldest first, removing them
+    /// from the buffer. Returns the number of bytes written.
+    ///
+    /// Each event is encoded as a little-endian `u32` timestamp, a `u8` kind,
+    /// a little-endian `u64` task id, a `u8` name length and the name
+    pub fn drain(&self, buf: &mut [u8]) -> usize {
+        let mut written = 0;
+
+        while self.len.get() > 0 {
+            let tail = (self.head.get() + N - self.len.get()) % N;
+            let event = unsafe { (*self.events.get())[tail] };
+            let n = event.encoded_len();
+            if written + n > buf.len() {
+                break;
+            }
+
+            event.encode(&mut buf[written..written + n]);
+            written += n;
+            self.pop();
+        }
+
+        written
+    }
+}
+
+impl<const N: usize> RuntimeHooks for Tracer<N> {
+    fn on_spawn(&self, task: &TaskInfo) {
+        self.record(EventKind::Spawn, Some(task))
+    }
+
+    fn on_wake(&self, task: &TaskInfo) {
+        self.record(EventKind::Wake, Some(task))
+    }
+
+    fn on_poll(&self, task: &TaskInfo) {
+        self.record(EventKind::PollStart, Some(task))
+    }
+
+    fn on_idle(&self, task: &TaskInfo) {
+        self.record(EventKind::PollEnd, Some(task))
+    }
+
+    fn on_complete(&self, task: &TaskInfo) {
+        self.record(EventKind::Complete, Some(task))
+    }
+
+    fn on_abort(&self, task: &TaskInfo) {
+        self.record(EventKind::Abort, Some(task))
+    }
+
+    fn on_timer(&self, task: &TaskInfo) {
+        self.record(EventKind::Timer, Some(task))
+    }
+
+    fn on_park(&self) {
+        self.record(EventKind::IdleEnter, None)
+    }
+
+    fn on_unpark(&self) {
+        self.record(EventKind::IdleExit, None)
+    }
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl<const N: usize> Sync for Tracer<N> {}
+
+// ===== impl Event =====
+
+impl Event {
+    const EMPTY: Event = Event {
+        timestamp: 0,
+        kind: EventKind::Spawn,
+        task: 0,
+        name: None,
+    };
+
+    fn name_len(&self) -> usize {
+        self.name.map(|n| n.len().min(u8::MAX as usize)).unwrap_or(0)
+    }
+
+    /// Number of bytes the event takes up when encoded
+    pub fn encoded_len(&self) -> usize {
+        4 + 1 + 8 + 1 + self.name_len()
+    }
+
+    fn encode(&self, buf: &mut [u8]) {
+        let name_len = self.name_len();
+        buf[0..4].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[4] = self.kind as u8;
+        buf[5..13].copy_from_slice(&self.task.to_le_bytes());
+        buf[13] = name_len as u8;
+        if let Some(name) = self.name {
+            buf[14..14 + name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+        }
+    }
+}
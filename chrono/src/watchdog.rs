@@ -0,0 +1,206 @@
+//! Feeds a hardware watchdog only while every registered task is alive.
+//!
+//! Tasks call [`register`] to promise they will check in at least once per
+//! period. Each time the runtime wakes up it checks the check-ins and feeds
+//! the [`Watchdog`] only if none are overdue. A task that hangs stops the
+//! feeding and the watchdog resets the device.
+
+use core::cell::RefCell;
+
+use heapless::Vec;
+
+use crate::runtime::context;
+use crate::task::TaskId;
+use crate::time::{Duration, Instant};
+
+/// Maximum number of check-ins that can be registered at once
+pub const MAX_CHECK_INS: usize = 16;
+
+static MONITOR: Monitor = Monitor {
+    inner: RefCell::new(Inner {
+        watchdog: None,
+        entries: [None; MAX_CHECK_INS],
+    }),
+};
+
+/// A watchdog fed by the runtime
+pub trait Watchdog {
+    /// Feed the watchdog
+    fn feed(&mut self);
+
+    /// How often the runtime has to feed the watchdog. This should be well
+    /// below the watchdog's timeout
+    fn period(&self) -> Duration;
+
+    /// Called when a task misses its check-in
+    fn missed(&mut self, _id: Option<TaskId>, _name: Option<&'static str>) {}
+}
+
+/// Registration of a task with the watchdog. Unregisters when dropped
+pub struct CheckIn {
+    slot: usize,
+}
+
+#[derive(Debug)]
+pub enum RegisterError {
+    /// All check-in slots are in use
+    Full,
+}
+
+/// Check-ins missed during a service of the watchdog
+type Missed = Vec<(Option<TaskId>, Option<&'static str>), MAX_CHECK_INS>;
+
+struct Monitor {
+    inner: RefCell<Inner>,
+}
+
+struct Inner {
+    watchdog: Option<&'static mut dyn Watchdog>,
+    entries: [Option<Entry>; MAX_CHECK_INS],
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// The task that registered the check-in. `None` outside of a task
+    id: Option<TaskId>,
+    name: Option<&'static str>,
+    period: Duration,
+    last: Instant,
+    /// Whether the miss has been reported
+    missed: bool,
+}
+
+// Safe since we are in a single-threaded environment
+unsafe impl Sync for Monitor {}
+
+// ===== impl Inner =====
+
+impl Inner {
+    /// Find overdue check-ins. Returns whether every task is alive, along
+    /// with the check-ins missed since the last time
+    fn check(&mut self, now: Instant) -> (bool, Missed) {
+        let mut alive = true;
+        let mut missed = Vec::new();
+        for entry in self.entries.iter_mut().flatten() {
+            // Alive if it checked in within the last period. The elapsed
+            // time is right even if the counter wrapped since the check-in
+            if now - entry.last <= entry.period {
+                continue;
+            }
+
+            alive = false;
+            if !entry.missed {
+                entry.missed = true;
+                defmt::error!(
+                    "Task {} ({}) missed its watchdog check-in",
+                    entry.id,
+                    entry.name
+                );
+                // Cannot fail since there are as many entries
+                let _ = missed.push((entry.id, entry.name));
+            }
+        }
+
+        (alive, missed)
+    }
+
+    /// When the runtime has to wake up next to service the watchdog, given
+    /// it was just serviced at `now`. The service point moves forward even
+    /// while the watchdog is not fed, so the runtime never spins on a past
+    /// deadline while waiting for the reset
+    fn deadline(&self, now: Instant) -> Option<Instant> {
+        let watchdog = self.watchdog.as_ref()?;
+        Some(now + watchdog.period())
+    }
+}
+
+// ===== impl CheckIn =====
+
+impl CheckIn {
+    /// Tell the watchdog the task is alive
+    pub fn check_in(&self) {
+        let mut inner = MONITOR.inner.borrow_mut();
+        if let Some(entry) = inner.entries[self.slot].as_mut() {
+            entry.last = Instant::now();
+            entry.missed = false;
+        }
+    }
+}
+
+impl Drop for CheckIn {
+    fn drop(&mut self) {
+        MONITOR.inner.borrow_mut().entries[self.slot] = None;
+    }
+}
+
+// ===== Functions =====
+
+/// Install the watchdog fed by the runtime
+pub fn set_watchdog(watchdog: &'static mut dyn Watchdog) {
+    let mut inner = MONITOR.inner.borrow_mut();
+    watchdog.feed();
+    inner.watchdog = Some(watchdog);
+}
+
+/// Register the current task with the watchdog. The task must call
+/// [`CheckIn::check_in`] at least once every `period`, otherwise the
+/// watchdog is no longer fed
+pub fn register(period: Duration) -> Result<CheckIn, RegisterError> {
+    let (id, name) = match context::current_task() {
+        Some(task) => {
+            let info = unsafe { task.as_ref().info() };
+            (Some(info.id), info.name)
+        }
+        None => (None, None),
+    };
+
+    let mut inner = MONITOR.inner.borrow_mut();
+    let slot = inner
+        .entries
+        .iter()
+        .position(Option::is_none)
+        .ok_or(RegisterError::Full)?;
+
+    inner.entries[slot] = Some(Entry {
+        id,
+        name,
+        period,
+        last: Instant::now(),
+        missed: false,
+    });
+    defmt::debug!("Task {} registered with the watchdog", id);
+
+    Ok(CheckIn { slot })
+}
+
+/// Check all check-ins and feed the watchdog if every task is alive
+pub(crate) fn service(now: Instant) {
+    let (alive, missed, watchdog) = {
+        let mut inner = MONITOR.inner.borrow_mut();
+        let (alive, missed) = inner.check(now);
+        (alive, missed, inner.watchdog.take())
+    };
+
+    let watchdog = match watchdog {
+        Some(watchdog) => watchdog,
+        None => return,
+    };
+
+    // The callbacks run without the monitor borrowed so they are free to
+    // check in or drop a check-in
+    for (id, name) in missed {
+        watchdog.missed(id, name);
+    }
+    if alive {
+        watchdog.feed();
+    }
+
+    // Unless a new watchdog was installed in the meantime
+    MONITOR.inner.borrow_mut().watchdog.get_or_insert(watchdog);
+}
+
+/// When the runtime has to wake up next to service the watchdog, given it
+/// was just serviced at `now`
+pub(crate) fn deadline(now: Instant) -> Option<Instant> {
+    MONITOR.inner.borrow().deadline(now)
+}
@@ -53,5 +53,14 @@ async fn main() -> ! {
         chrono::spawn(handle_tcp_conn()).unwrap();
     }
 
-    stack.await;
+    match stack.await {
+        Ok(()) => defmt::error!("Net daemon stopped"),
+        Err(e) => defmt::error!("Net daemon stopped: {}", e),
+    }
+
+    // Connections can't be served without the net daemon. Park rather than
+    // take the board down
+    loop {
+        core::future::pending::<()>().await
+    }
 }
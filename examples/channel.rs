@@ -15,7 +15,7 @@ const chan_size: usize = 2;
 #[chrono::alloc]
 async fn send(tx: Sender<'static, &str, chan_size>) -> u8 {
     defmt::info!("Sending message from task 1");
-    tx.send("task 1: fly.io").unwrap();
+    tx.send("task 1: fly.io").await.unwrap();
     5
 }
 
@@ -16,7 +16,7 @@ const chan_size: usize = 2;
 #[chrono::alloc]
 async fn send1(tx: Sender<'static, &str, chan_size>) {
     defmt::info!("Sending message from task 1");
-    tx.send("hello").unwrap();
+    tx.send("hello").await.unwrap();
 }
 
 #[chrono::alloc]
@@ -24,7 +24,7 @@ async fn send2(tx: Sender<'static, &str, chan_size>) {
     defmt::info!("Sending message from handle one after sleeping");
     sleep(Duration::from_secs(1)).await;
     defmt::info!("Done sleeping. Sending message from handle one");
-    tx.send("hello world").unwrap();
+    tx.send("hello world").await.unwrap();
     defmt::info!("Sent message!")
 }
 
@@ -20,8 +20,8 @@ fn main() -> ! {
     let h1 = chrono::spawn(delay(Duration::from_secs(5))).unwrap();
     let h2 = chrono::spawn(delay(Duration::from_secs(1))).unwrap();
 
-    h2.await;
-    h1.await;
+    h2.await.unwrap();
+    h1.await.unwrap();
 
     let later = Instant::now();
     let elapsed = later - now;
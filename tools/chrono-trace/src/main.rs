@@ -0,0 +1,408 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+/// Marker the device prints in front of every event when dumping over defmt
+const DUMP_MARKER: &str = "chrono-trace";
+
+/// Process id used for every event. The device only has one executor
+const PID: u32 = 1;
+
+/// Thread id used for events of the runtime itself
+const RUNTIME_TID: u64 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Spawn,
+    PollStart,
+    PollEnd,
+    Wake,
+    Timer,
+    Complete,
+    IdleEnter,
+    IdleExit,
+    Abort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    timestamp: u32,
+    kind: Kind,
+    task: u64,
+    name: Option<String>,
+}
+
+// ===== impl Kind =====
+
+impl Kind {
+    fn from_u8(kind: u8) -> Option<Kind> {
+        let kind = match kind {
+            0 => Kind::Spawn,
+            1 => Kind::PollStart,
+            2 => Kind::PollEnd,
+            3 => Kind::Wake,
+            4 => Kind::Timer,
+            5 => Kind::Complete,
+            6 => Kind::IdleEnter,
+            7 => Kind::IdleExit,
+            8 => Kind::Abort,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+// ===== Parsing =====
+
+/// Parse the defmt output of `Tracer::dump`. Lines without the marker are skipped
+fn parse_text(input: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    for line in input.lines() {
+        let rest = match line.find(DUMP_MARKER) {
+            Some(idx) => &line[idx + DUMP_MARKER.len()..],
+            None => continue,
+        };
+
+        // The name is printed last and may contain spaces. It is empty for
+        // most events
+        let (timestamp, rest) = next_field(rest);
+        let (kind, rest) = next_field(rest);
+        let (task, name) = next_field(rest);
+
+        let timestamp = timestamp.and_then(|f| f.parse().ok());
+        let kind = kind.and_then(|f| f.parse().ok()).and_then(Kind::from_u8);
+        let task = task.and_then(|f| f.parse().ok());
+        let name = match name.trim() {
+            "" => None,
+            name => Some(name.to_string()),
+        };
+
+        match (timestamp, kind, task) {
+            (Some(timestamp), Some(kind), Some(task)) => events.push(Event {
+                timestamp,
+                kind,
+                task,
+                name,
+            }),
+            // Lines such as the dropped events warning
+            _ => continue,
+        }
+    }
+
+    events
+}
+
+/// Split the next whitespace separated field off `s`
+fn next_field(s: &str) -> (Option<&str>, &str) {
+    let s = s.trim_start();
+    if s.is_empty() {
+        return (None, s);
+    }
+    match s.find(char::is_whitespace) {
+        Some(idx) => (Some(&s[..idx]), &s[idx..]),
+        None => (Some(s), ""),
+    }
+}
+
+/// Parse the bytes produced by `Tracer::drain`
+fn parse_binary(input: &[u8]) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        if rest.len() < 14 {
+            return Err(format!("truncated event: {} bytes left", rest.len()));
+        }
+
+        let timestamp = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+        let kind = Kind::from_u8(rest[4]).ok_or(format!("unknown event kind {}", rest[4]))?;
+        let task = u64::from_le_bytes(rest[5..13].try_into().unwrap());
+        let name_len = rest[13] as usize;
+        if rest.len() < 14 + name_len {
+            return Err("truncated task name".to_string());
+        }
+
+        let name = match name_len {
+            0 => None,
+            _ => Some(String::from_utf8_lossy(&rest[14..14 + name_len]).into_owned()),
+        };
+
+        events.push(Event {
+            timestamp,
+            kind,
+            task,
+            name,
+        });
+        rest = &rest[14 + name_len..];
+    }
+
+    Ok(events)
+}
+
+// ===== Chrome trace =====
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn thread_name(tid: u64, name: &str) -> String {
+    format!(
+        r#"{{"name":"thread_name","ph":"M","pid":{},"tid":{},"args":{{"name":"{}"}}}}"#,
+        PID,
+        tid,
+        escape(name)
+    )
+}
+
+fn span(ph: char, name: &str, ts: u64, tid: u64) -> String {
+    format!(
+        r#"{{"name":"{}","ph":"{}","ts":{},"pid":{},"tid":{}}}"#,
+        escape(name),
+        ph,
+        ts,
+        PID,
+        tid
+    )
+}
+
+fn instant(name: &str, ts: u64, tid: u64) -> String {
+    format!(
+        r#"{{"name":"{}","ph":"i","s":"t","ts":{},"pid":{},"tid":{}}}"#,
+        escape(name),
+        ts,
+        PID,
+        tid
+    )
+}
+
+/// Convert events into Chrome trace JSON. Each task gets its own track, and
+/// the executor's idle periods are shown on a separate track
+fn to_chrome_trace(events: &[Event]) -> String {
+    let mut out = Vec::new();
+    out.push(thread_name(RUNTIME_TID, "executor"));
+
+    // Timestamps are the 32-bit cycle counter and wrap around. Unwrap them so
+    // the timeline stays monotonic
+    let mut epoch: u64 = 0;
+    let mut prev: Option<u32> = None;
+
+    for event in events {
+        if let Some(prev) = prev {
+            if event.timestamp < prev {
+                epoch += 1 << 32;
+            }
+        }
+        prev = Some(event.timestamp);
+
+        let ts = epoch + event.timestamp as u64;
+        let tid = event.task;
+        let task = format!("task {}", tid);
+
+        match event.kind {
+            Kind::Spawn => {
+                let name = match &event.name {
+                    Some(name) => format!("{} ({})", name, tid),
+                    None => task,
+                };
+                out.push(thread_name(tid, &name));
+                out.push(instant("spawn", ts, tid));
+            }
+            Kind::PollStart => out.push(span('B', "poll", ts, tid)),
+            Kind::PollEnd => out.push(span('E', "poll", ts, tid)),
+            Kind::Wake => out.push(instant("wake", ts, tid)),
+            Kind::Timer => out.push(instant("timer", ts, tid)),
+            Kind::Complete => {
+                out.push(span('E', "poll", ts, tid));
+                out.push(instant("complete", ts, tid));
+            }
+            // Tasks are aborted between polls, so there is no poll to end
+            Kind::Abort => out.push(instant("abort", ts, tid)),
+            Kind::IdleEnter => out.push(span('B', "idle", ts, RUNTIME_TID)),
+            Kind::IdleExit => out.push(span('E', "idle", ts, RUNTIME_TID)),
+        }
+    }
+
+    format!(
+        "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+        out.join(",\n")
+    )
+}
+
+// ===== main =====
+
+fn usage() -> ! {
+    eprintln!("usage: chrono-trace [--binary] [FILE]");
+    eprintln!();
+    eprintln!("Converts a chrono trace dump into Chrome trace JSON, written to stdout.");
+    eprintln!("Reads from stdin if no file is given.");
+    process::exit(2)
+}
+
+fn main() {
+    let mut binary = false;
+    let mut path = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--binary" => binary = true,
+            "-h" | "--help" => usage(),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let input = match &path {
+        Some(path) => fs::read(path),
+        None => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf).map(|_| buf)
+        }
+    };
+    let input = input.unwrap_or_else(|e| {
+        eprintln!("error: failed to read input: {}", e);
+        process::exit(1)
+    });
+
+    let events = if binary {
+        parse_binary(&input).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1)
+        })
+    } else {
+        parse_text(&String::from_utf8_lossy(&input))
+    };
+
+    if events.is_empty() {
+        eprintln!("warning: no events found");
+    }
+
+    let json = to_chrome_trace(&events);
+    io::stdout().write_all(json.as_bytes()).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_defmt_dump() {
+        let input = "\
+            0.000100 INFO  chrono-trace 100 0 1 netd\n\
+            └─ chrono::trace::{impl#0}::dump @ src/trace.rs:130\n\
+            0.000200 INFO  chrono-trace 200 1 1 \n\
+            0.000300 WARN  chrono-trace: 4 events dropped\n";
+
+        let events = parse_text(input);
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    timestamp: 100,
+                    kind: Kind::Spawn,
+                    task: 1,
+                    name: Some("netd".to_string())
+                },
+                Event {
+                    timestamp: 200,
+                    kind: Kind::PollStart,
+                    task: 1,
+                    name: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_name_with_spaces() {
+        let input = "0.000100 INFO  chrono-trace 100 0 1 tcp handler 2\n";
+
+        let events = parse_text(input);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name.as_deref(), Some("tcp handler 2"));
+    }
+
+    #[test]
+    fn parse_drained_bytes() {
+        let mut input = Vec::new();
+        input.extend_from_slice(&7u32.to_le_bytes());
+        input.push(0);
+        input.extend_from_slice(&3u64.to_le_bytes());
+        input.push(4);
+        input.extend_from_slice(b"netd");
+        input.extend_from_slice(&9u32.to_le_bytes());
+        input.push(6);
+        input.extend_from_slice(&0u64.to_le_bytes());
+        input.push(0);
+
+        let events = parse_binary(&input).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name.as_deref(), Some("netd"));
+        assert_eq!(events[1].kind, Kind::IdleEnter);
+
+        assert!(parse_binary(&input[..input.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn abort_ends_no_poll() {
+        let events = [
+            Event {
+                timestamp: 1,
+                kind: Kind::PollStart,
+                task: 1,
+                name: None,
+            },
+            Event {
+                timestamp: 2,
+                kind: Kind::PollEnd,
+                task: 1,
+                name: None,
+            },
+            Event {
+                timestamp: 3,
+                kind: Kind::Abort,
+                task: 1,
+                name: None,
+            },
+        ];
+        assert_eq!(Kind::from_u8(8), Some(Kind::Abort));
+
+        let json = to_chrome_trace(&events);
+        assert_eq!(json.matches(r#""ph":"B""#).count(), 1);
+        assert_eq!(json.matches(r#""ph":"E""#).count(), 1);
+        assert!(json.contains(r#"{"name":"abort","ph":"i","s":"t","ts":3,"pid":1,"tid":1}"#));
+    }
+
+    #[test]
+    fn unwraps_timestamps() {
+        let events = [
+            Event {
+                timestamp: u32::MAX,
+                kind: Kind::PollStart,
+                task: 1,
+                name: None,
+            },
+            Event {
+                timestamp: 1,
+                kind: Kind::PollEnd,
+                task: 1,
+                name: None,
+            },
+        ];
+
+        let json = to_chrome_trace(&events);
+        assert!(json.contains(&format!("\"ts\":{}", u32::MAX)));
+        assert!(json.contains(&format!("\"ts\":{}", (1u64 << 32) + 1)));
+    }
+}
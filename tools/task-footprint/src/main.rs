@@ -0,0 +1,316 @@
+use std::env;
+use std::fs;
+use std::process;
+
+/// Name of the static emitted by `#[chrono::alloc]` in every task function
+const FOOTPRINT_SYMBOL: &str = "__CHRONO_FOOTPRINT";
+
+/// Section the footprint statics are placed in
+const FOOTPRINT_SECTION: &str = ".chrono_footprint";
+
+const SHT_SYMTAB: u32 = 2;
+const STT_OBJECT: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Footprint {
+    task: String,
+    size: u32,
+    count: u32,
+}
+
+struct Section {
+    name: u32,
+    kind: u32,
+    addr: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+}
+
+// ===== ELF parsing =====
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| format!("unexpected end of file at {:#x}", offset))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| format!("unexpected end of file at {:#x}", offset))
+}
+
+fn str_at(data: &[u8], offset: usize) -> Result<&str, String> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| format!("string offset {:#x} out of bounds", offset))?;
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    std::str::from_utf8(&bytes[..end]).map_err(|e| e.to_string())
+}
+
+fn sections(elf: &[u8]) -> Result<Vec<Section>, String> {
+    if elf.get(0..4) != Some(b"\x7fELF") {
+        return Err("not an ELF file".to_string());
+    }
+    // 32-bit little endian, as produced for Cortex-M targets
+    if elf[4] != 1 || elf[5] != 1 {
+        return Err("only 32-bit little-endian ELF files are supported".to_string());
+    }
+
+    let shoff = u32_at(elf, 0x20)? as usize;
+    let shentsize = u16_at(elf, 0x2e)? as usize;
+    let shnum = u16_at(elf, 0x30)? as usize;
+
+    (0..shnum)
+        .map(|i| {
+            let base = shoff + i * shentsize;
+            Ok(Section {
+                name: u32_at(elf, base)?,
+                kind: u32_at(elf, base + 0x04)?,
+                addr: u32_at(elf, base + 0x0c)?,
+                offset: u32_at(elf, base + 0x10)?,
+                size: u32_at(elf, base + 0x14)?,
+                link: u32_at(elf, base + 0x18)?,
+            })
+        })
+        .collect()
+}
+
+/// Index of the section called `name`
+fn section_index(elf: &[u8], sections: &[Section], name: &str) -> Result<Option<usize>, String> {
+    let shstrndx = u16_at(elf, 0x32)? as usize;
+    let shstrtab = sections
+        .get(shstrndx)
+        .ok_or("missing section name table")?;
+
+    for (i, section) in sections.iter().enumerate() {
+        if str_at(elf, (shstrtab.offset + section.name) as usize)? == name {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+/// Read the size and count held by every static in the footprint section
+fn footprints(elf: &[u8]) -> Result<Vec<Footprint>, String> {
+    let sections = sections(elf)?;
+    let index = match section_index(elf, &sections, FOOTPRINT_SECTION)? {
+        Some(index) => index,
+        None => return Ok(Vec::new()),
+    };
+    let section = &sections[index];
+
+    let symtab = sections
+        .iter()
+        .find(|s| s.kind == SHT_SYMTAB)
+        .ok_or("no symbol table. Was the binary stripped?")?;
+    let strtab = sections
+        .get(symtab.link as usize)
+        .ok_or("missing string table")?;
+
+    let mut footprints = Vec::new();
+    for i in 0..(symtab.size / 16) as usize {
+        let base = symtab.offset as usize + i * 16;
+        let name = u32_at(elf, base)?;
+        let value = u32_at(elf, base + 4)?;
+        let info = elf[base + 12];
+        let shndx = u16_at(elf, base + 14)? as usize;
+        if shndx != index || info & 0xf != STT_OBJECT {
+            continue;
+        }
+
+        let name = str_at(elf, (strtab.offset + name) as usize)?;
+        let offset = (section.offset + value - section.addr) as usize;
+
+        footprints.push(Footprint {
+            task: task_path(name),
+            size: u32_at(elf, offset)?,
+            count: u32_at(elf, offset + 4)?,
+        });
+    }
+
+    footprints.sort_by_key(|f| std::cmp::Reverse(f.size * f.count));
+    Ok(footprints)
+}
+
+// ===== Symbol names =====
+
+/// Demangle a legacy Rust symbol such as `_ZN4echo4netd18__CHRONO_FOOTPRINT17h0123456789abcdefE`
+/// into `echo::netd`. Other names are returned as is
+fn task_path(symbol: &str) -> String {
+    let mut rest = match symbol.strip_prefix("_ZN") {
+        Some(rest) => rest,
+        None => return symbol.to_string(),
+    };
+
+    let mut parts = Vec::new();
+    while let Some(len_end) = rest.find(|c: char| !c.is_ascii_digit()) {
+        if len_end == 0 {
+            break;
+        }
+        let len: usize = rest[..len_end].parse().unwrap();
+        let part = match rest.get(len_end..len_end + len) {
+            Some(part) => part,
+            None => return symbol.to_string(),
+        };
+        parts.push(unescape(part));
+        rest = &rest[len_end + len..];
+    }
+
+    // Drop the hash and the name of the static itself
+    parts.retain(|p| !(p.starts_with('h') && p.len() == 17) && p != FOOTPRINT_SYMBOL);
+    parts.join("::")
+}
+
+fn unescape(part: &str) -> String {
+    let part = part.strip_prefix('_').filter(|p| p.starts_with('$')).unwrap_or(part);
+    part.replace("$LT$", "<")
+        .replace("$GT$", ">")
+        .replace("$u20$", " ")
+        .replace("$C$", ",")
+        .replace("..", "::")
+}
+
+// ===== main =====
+
+fn usage() -> ! {
+    eprintln!("usage: task-footprint ELF");
+    eprintln!();
+    eprintln!("Lists the RAM taken by the task pool of every #[chrono::alloc] function.");
+    process::exit(2)
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(arg) if arg == "-h" || arg == "--help" => usage(),
+        Some(path) => path,
+        None => usage(),
+    };
+
+    let elf = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("error: failed to read {}: {}", path, e);
+        process::exit(1)
+    });
+
+    let footprints = footprints(&elf).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1)
+    });
+
+    if footprints.is_empty() {
+        eprintln!("warning: no task footprints found");
+        return;
+    }
+
+    let width = footprints.iter().map(|f| f.task.len()).max().unwrap_or(0).max(4);
+    println!("{:<width$}  {:>6} {:>6} {:>7}", "TASK", "SIZE", "COUNT", "TOTAL");
+
+    let mut total = 0;
+    for f in &footprints {
+        match f.count {
+            0 => println!("{:<width$}  {:>6} {:>6} {:>7}", f.task, f.size, "arena", "-"),
+            count => {
+                total += f.size * count;
+                println!("{:<width$}  {:>6} {:>6} {:>7}", f.task, f.size, count, f.size * count);
+            }
+        }
+    }
+    println!("{:<width$}  {:>6} {:>6} {:>7}", "TOTAL", "", "", total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangles_task_path() {
+        assert_eq!(
+            task_path("_ZN4echo15handle_tcp_conn18__CHRONO_FOOTPRINT17h0c50aeb569425822E"),
+            "echo::handle_tcp_conn"
+        );
+        assert_eq!(task_path("__CHRONO_FOOTPRINT"), "__CHRONO_FOOTPRINT");
+    }
+
+    /// A minimal ELF with a section called `section` holding one footprint
+    /// and a symbol table pointing at it
+    fn elf(section_name: &str, symbol: &str, size: u32, count: u32) -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        strtab.extend_from_slice(symbol.as_bytes());
+        strtab.push(0);
+
+        let mut shstrtab = vec![0u8];
+        shstrtab.extend_from_slice(section_name.as_bytes());
+        shstrtab.push(0);
+
+        let mut footprint = Vec::new();
+        footprint.extend_from_slice(&size.to_le_bytes());
+        footprint.extend_from_slice(&count.to_le_bytes());
+
+        let mut symtab = vec![0u8; 16];
+        symtab.extend_from_slice(&1u32.to_le_bytes()); // name
+        symtab.extend_from_slice(&4u32.to_le_bytes()); // value
+        symtab.extend_from_slice(&8u32.to_le_bytes()); // size
+        symtab.extend_from_slice(&[STT_OBJECT, 0]); // info, other
+        symtab.extend_from_slice(&1u16.to_le_bytes()); // section index
+
+        let mut data = vec![0u8; 0x34];
+        data[0..4].copy_from_slice(b"\x7fELF");
+        data[4] = 1;
+        data[5] = 1;
+
+        let footprint_off = data.len() as u32;
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&footprint);
+        let symtab_off = data.len() as u32;
+        data.extend_from_slice(&symtab);
+        let strtab_off = data.len() as u32;
+        data.extend_from_slice(&strtab);
+        let shstrtab_off = data.len() as u32;
+        data.extend_from_slice(&shstrtab);
+
+        let shoff = data.len() as u32;
+        data[0x20..0x24].copy_from_slice(&shoff.to_le_bytes());
+        data[0x2e..0x30].copy_from_slice(&40u16.to_le_bytes());
+        data[0x30..0x32].copy_from_slice(&5u16.to_le_bytes());
+        data[0x32..0x34].copy_from_slice(&4u16.to_le_bytes());
+
+        let section = |name: u32, kind: u32, offset: u32, size: u32, link: u32| {
+            let mut s = vec![0u8; 40];
+            s[0x00..0x04].copy_from_slice(&name.to_le_bytes());
+            s[0x04..0x08].copy_from_slice(&kind.to_le_bytes());
+            s[0x10..0x14].copy_from_slice(&offset.to_le_bytes());
+            s[0x14..0x18].copy_from_slice(&size.to_le_bytes());
+            s[0x18..0x1c].copy_from_slice(&link.to_le_bytes());
+            s
+        };
+        // Not loaded onto the device, so the section has no address
+        data.extend(section(0, 0, 0, 0, 0));
+        data.extend(section(1, 1, footprint_off, 12, 0));
+        data.extend(section(0, SHT_SYMTAB, symtab_off, symtab.len() as u32, 3));
+        data.extend(section(0, 3, strtab_off, strtab.len() as u32, 0));
+        data.extend(section(0, 3, shstrtab_off, shstrtab.len() as u32, 0));
+        data
+    }
+
+    #[test]
+    fn reads_footprint_from_elf() {
+        let symbol = "_ZN4echo4netd18__CHRONO_FOOTPRINT17h5b2d5644b3239db4E";
+        let elf = elf(FOOTPRINT_SECTION, symbol, 208, 3);
+        assert_eq!(
+            footprints(&elf).unwrap(),
+            vec![Footprint {
+                task: "echo::netd".to_string(),
+                size: 208,
+                count: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn ignores_other_sections() {
+        let symbol = "_ZN4echo4netd18__CHRONO_FOOTPRINT17h5b2d5644b3239db4E";
+        let elf = elf(".rodata", symbol, 208, 3);
+        assert_eq!(footprints(&elf).unwrap(), vec![]);
+    }
+}